@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+
+// Sysroot from `rustc [+toolchain] --print sysroot`, the toolchain can also be the path of the sysroot itself
+pub fn find_sysroot(toolchain: Option<&str>) -> Result<PathBuf> {
+    if let Some(toolchain) = toolchain {
+        if Path::new(toolchain).is_dir() {
+            return Ok(PathBuf::from(toolchain));
+        }
+    }
+
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+
+    let mut command = Command::new(rustc.as_str());
+
+    // The `+toolchain` argument is handled by the rustup proxy
+    if let Some(toolchain) = toolchain {
+        command.arg(format!("+{}", toolchain));
+    }
+
+    let output = command
+        .args(["--print", "sysroot"])
+        .output()
+        .with_context(|| format!("Failed to run {} to find the sysroot", rustc))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to find the sysroot of toolchain {}: {}",
+            toolchain.unwrap_or("default"),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+// Cargo.lock of the standard library from the `rust-src` component of the sysroot,
+// it is in the `library` folder since Rust 1.56 and in the source root before that
+pub fn find_std_lock_file(sysroot: &Path) -> Result<PathBuf> {
+    let rust_src = sysroot.join("lib").join("rustlib").join("src").join("rust");
+
+    [rust_src.join("library").join("Cargo.lock"), rust_src.join("Cargo.lock")]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| anyhow!(
+            "Cargo.lock of the standard library was not found in {}, install it with `rustup component add rust-src`",
+            rust_src.display()
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn find_std_lock_file_in_library_and_old_layout() {
+        let tmp_dir = tempdir::TempDir::new("build_std_test").expect("Failed to create temp dir");
+        let rust_src = tmp_dir.path().join("lib/rustlib/src/rust");
+
+        assert_eq!(find_std_lock_file(tmp_dir.path()).is_err(), true);
+
+        fs::create_dir_all(rust_src.join("library")).expect("Failed to create dir");
+        fs::write(rust_src.join("Cargo.lock"), "").expect("Failed to write file");
+        assert_eq!(find_std_lock_file(tmp_dir.path()).expect("Lock file exists"), rust_src.join("Cargo.lock"));
+
+        fs::write(rust_src.join("library/Cargo.lock"), "").expect("Failed to write file");
+        assert_eq!(find_std_lock_file(tmp_dir.path()).expect("Lock file exists"), rust_src.join("library/Cargo.lock"));
+    }
+
+    #[test]
+    fn find_sysroot_from_path_and_rustc() {
+        let tmp_dir = tempdir::TempDir::new("build_std_test").expect("Failed to create temp dir");
+
+        assert_eq!(
+            find_sysroot(Some(tmp_dir.path().to_str().unwrap())).expect("Sysroot exists"),
+            tmp_dir.path().to_path_buf()
+        );
+        assert_eq!(find_sysroot(None).expect("rustc exists").join("lib").is_dir(), true);
+    }
+}
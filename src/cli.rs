@@ -1,14 +1,24 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use anyhow::Context;
-use clap::Parser;
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use itertools::Itertools;
+use semver::VersionReq;
 use tracing::subscriber::set_global_default as set_global_subscriber;
 use tracing_subscriber::fmt::time::SystemTime;
 use tracing_subscriber::FmtSubscriber;
 
 const EXAMPLES: &str = r#"
 Examples:
+    # Collect the dependencies of the crates `serde`, `tokio` and `anyhow` together
+    # with the `derive` feature of serde (only the optional dependencies of enabled features are collected)
+    ./cargo-collect serde@^1 tokio@1.35 anyhow --features serde/derive
+
+    # Collect the dependencies of all the crates in list file with lines like `serde@^1 features=derive`
+    ./cargo-collect --from-list deps.txt
+
     # Collect the dependencies of the crate `serde` with version requirement `^1.0.0`
     # Save the crates files in "./deps" folder
     ./cargo-collect --crate-name serde --crate-version-req "^1.0.0"
@@ -23,29 +33,110 @@ Examples:
     # that is not published to crates.io and it's required in order to install the library
     ./cargo-collect --cargo-file Cargo.toml
 
+    # Collect only the dependencies of the `cli` and `server` members of the workspace
+    ./cargo-collect --cargo-file Cargo.toml -p cli -p server
+
     # Collect all dependencies used by the local Cargo.lock file that match the EXACT
     # versions specified.
     # Useful for example when some python library (e.g. cryptography) have Rust implementation
     # that is not published to crates.io and it's required in order to install the library
     ./cargo-collect --cargo-lock-file Cargo.lock
+
+    # Collect the dependencies of all the Rust projects in monorepo (every Cargo.lock and Cargo.toml without lock file)
+    ./cargo-collect --scan ./monorepo
+
+    # Collect the dependencies of several inputs together, all of them are collected in one pass
+    ./cargo-collect serde@^1 --cargo-lock-file app/Cargo.lock --cargo-lock-file tool/Cargo.lock --cargo-file lib/Cargo.toml
+
+    # Collect the exact packages that cargo resolved for the local project (runs `cargo metadata`),
+    # or take them from saved `cargo metadata --format-version 1` output
+    ./cargo-collect --cargo-metadata Cargo.toml
+    ./cargo-collect --cargo-metadata metadata.json
+
+    # Collect the dependencies of the standard library of the nightly toolchain for building with `-Z build-std`
+    # (requires the `rust-src` component)
+    ./cargo-collect --build-std nightly
+
+    # Collect the dependencies of the Rust extension of python package from its source distribution
+    ./cargo-collect --python-sdist cryptography-42.0.0.tar.gz
+
+    # Collect exactly the cargo packages that CycloneDX or SPDX SBOM declares
+    ./cargo-collect --sbom product.cdx.json --no-index
+
+    # Collect the dependencies of crate file that was not published (from its Cargo.lock if it has one)
+    ./cargo-collect --crate-file foo-1.2.3.crate
+
+    # Collect the exact packages of the Cargo.lock that is published with ripgrep for `cargo install ripgrep --locked`
+    ./cargo-collect install ripgrep@14 --locked
+
+    # Collect everything that was installed with `cargo install` for provisioning offline machine with the same tools
+    ./cargo-collect --installed
+
+    # Collect only the packages of Cargo.lock that the `cli` member depends on
+    ./cargo-collect --cargo-lock-file Cargo.lock -p cli
+
+    # Print the chains of dependencies that bring `serde` 0.9 into Cargo.lock (without the index)
+    ./cargo-collect why serde@0.9 --cargo-lock-file Cargo.lock
+
+    # Collect the dependencies of the crate `my-crate` from private registry
+    # that is configured in cargo config under `[registries.my-registry]`
+    ./cargo-collect --crate-name my-crate --registry my-registry
+
+    # List the dependencies of the local Cargo.lock file that are missing from a previous collection
+    # without using the crates.io index
+    ./cargo-collect --cargo-lock-file Cargo.lock --resolve-from deps
+
+    # Collect all dependencies of the local Cargo.lock file without cloning the crates.io index
+    ./cargo-collect --cargo-lock-file Cargo.lock --no-index
+
+    # Collect the dependencies of the local Cargo.toml file that are needed on linux and windows only
+    ./cargo-collect --cargo-file Cargo.toml --target x86_64-unknown-linux-gnu --target x86_64-pc-windows-msvc
+
+    # Collect the dependencies of the crate `my-crate` with the index entries from external provider
+    ./cargo-collect --crate-name my-crate --index-provider "./my-provider --store https://store.local"
 "#;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, after_help = EXAMPLES)]
+#[command(group(
+    ArgGroup::new("input")
+        .args(["crate_name", "crates", "from_list", "cargo_file", "cargo_lock_file", "cargo_metadata", "build_std", "crate_file", "python_sdist", "scan", "sbom", "installed"])
+        .required(true)
+        .multiple(true)
+))]
+#[command(group(ArgGroup::new("members_input").args(["cargo_file", "cargo_lock_file"]).multiple(true)))]
+#[command(group(ArgGroup::new("lock_file").args(["cargo_lock_file", "build_std", "sbom"]).multiple(true)))]
+#[command(subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// The crate name.
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
+    /// Crates to collect as `name` or `name@version_req` (e.g. `serde@^1 tokio@1.35 anyhow`).
+    /// Without version requirement the highest version is taken
+    #[arg(value_name = "CRATE[@VERSION_REQ]")]
+    pub(crate) crates: Vec<CrateSpec>,
+
+    /// Features to enable as `crate/feature`, comma separated or used multiple times
+    /// (just `feature` when collecting single crate).
+    ///
+    /// Only the optional dependencies of the default and given features are collected for crates with features,
+    /// all optional dependencies are collected for other crates
     #[arg(
-        short = 'n',
+        short = 'F',
         long,
-        required_unless_present_any(["cargo_lock_file", "cargo_file"])
+        value_delimiter = ','
     )]
+    pub(crate) features: Vec<String>,
+
+    /// The crate name.
+    #[arg(short = 'n', long)]
     pub(crate) crate_name: Option<String>,
 
     /// The version requirement of the crate can be =1.0.0 or ^1.0 (see semver.org).
     #[arg(
         short = 'v',
         long,
-        conflicts_with_all(["cargo_lock_file", "cargo_file"])
+        requires = "crate_name"
     )]
     pub(crate) crate_version_req: Option<String>,
 
@@ -53,6 +144,7 @@ pub struct Cli {
     #[arg(
         short = 'o',
         long,
+        global = true,
         default_value = "deps"
     )]
     pub(crate) output: PathBuf,
@@ -61,21 +153,84 @@ pub struct Cli {
     /// This will take the latest version that the version requirement
     /// (This should be used when the crate is not published)
     ///
-    /// Support workspaces
-    #[arg(
-        long,
-        required_unless_present_any(["crate_name", "cargo_lock_file"])
-    )]
-    pub(crate) cargo_file: Option<String>,
+    /// Support workspaces, including virtual workspaces (the dependencies of all members are collected).
+    /// Can be used multiple times
+    #[arg(long)]
+    pub(crate) cargo_file: Vec<String>,
+
+    /// Collect only the dependencies of these members of the workspace that the Cargo.toml file belongs to,
+    /// the Cargo.toml file can be the workspace root or any of its members.
+    ///
+    /// With Cargo.lock file only the packages that these local packages depend on are collected. Can be used multiple times
+    #[arg(short = 'p', long, requires = "members_input")]
+    pub(crate) package: Vec<String>,
 
     /// The Cargo.lock file to take dependencies from.
     /// This will take exact versions of the dependencies.
     /// (This should be used when the crate is not published)
+    ///
+    /// The checksums of the lock file are verified against the index and the crate files,
+    /// the collection fails on mismatch. Can be used multiple times
+    #[arg(long)]
+    pub(crate) cargo_lock_file: Vec<String>,
+
+    /// The output of `cargo metadata --format-version 1` to take the resolved dependencies from,
+    /// or Cargo.toml file to run `cargo metadata` on (requires cargo).
+    ///
+    /// This will take the exact versions and sources of the packages in the `resolve` section
+    #[arg(long)]
+    pub(crate) cargo_metadata: Vec<PathBuf>,
+
+    /// Collect the dependencies of the standard library for building with `-Z build-std`,
+    /// from the Cargo.lock of the `rust-src` component of the toolchain (e.g. `nightly`) or sysroot folder.
+    ///
+    /// The default toolchain is used without value
     #[arg(
         long,
-        required_unless_present_any(["crate_name", "cargo_file"])
+        value_name = "TOOLCHAIN",
+        num_args = 0..=1
     )]
-    pub(crate) cargo_lock_file: Option<String>,
+    pub(crate) build_std: Option<Option<String>>,
+
+    /// The `.crate` file to take the dependencies from, without extracting or publishing it.
+    ///
+    /// The exact versions are taken from its Cargo.lock when the crate has one,
+    /// otherwise the dependencies are taken from its Cargo.toml
+    #[arg(long)]
+    pub(crate) crate_file: Vec<PathBuf>,
+
+    /// The python source distribution (`.tar.gz`) of package with Rust extension (e.g. `cryptography`) to take the dependencies from.
+    ///
+    /// The Rust manifests are found from the build backend config (maturin `manifest-path` or setuptools-rust extensions),
+    /// the exact versions are taken from their Cargo.lock when the sdist has one
+    #[arg(long)]
+    pub(crate) python_sdist: Vec<PathBuf>,
+
+    /// Folder to scan recursively for Rust projects, every Cargo.lock and every Cargo.toml without Cargo.lock are collected.
+    ///
+    /// Files that are ignored by `.gitignore` are skipped. Can be used multiple times
+    #[arg(long)]
+    pub(crate) scan: Vec<PathBuf>,
+
+    /// CycloneDX or SPDX JSON SBOM to take the exact packages from, every component with `pkg:cargo/name@version` package url.
+    ///
+    /// The SHA-256 hashes of the SBOM are verified like the checksums of Cargo.lock,
+    /// with `--no-index` exactly the packages of the SBOM are collected. Can be used multiple times
+    #[arg(long)]
+    pub(crate) sbom: Vec<PathBuf>,
+
+    /// Collect the packages that were installed with `cargo install`, from `$CARGO_HOME/.crates2.json`.
+    ///
+    /// The exact versions are taken from the Cargo.lock that is published with each package when it has one,
+    /// the installed packages are downloaded in their own pass for reading their Cargo.lock
+    #[arg(long)]
+    pub(crate) installed: bool,
+
+    /// File with list of crates to collect, each line is `name[@version_req][ features=a,b]`.
+    ///
+    /// Empty lines and comments that start with `#` are ignored
+    #[arg(long)]
+    pub(crate) from_list: Vec<PathBuf>,
 
     /// Whether to update the local index of crates.io.
     ///
@@ -83,9 +238,158 @@ pub struct Cli {
     #[arg(
         short = 'u',
         long,
+        global = true,
         default_value = "false"
     )]
     pub(crate) update_index: bool,
+
+    /// The registry to collect the crates from instead of crates.io.
+    ///
+    /// The registry must be configured in cargo config under `[registries.<name>]`,
+    /// the token for registries that require authentication is taken the same way cargo does
+    /// (`credentials.toml`, `CARGO_REGISTRIES_<NAME>_TOKEN` or credential provider)
+    #[arg(long, global = true)]
+    pub(crate) registry: Option<String>,
+
+    /// Resolve the dependencies only from the crate files in existing output folder
+    /// of previous collection instead of the crates.io index.
+    ///
+    /// Nothing is downloaded, the crates that are missing from the folder are printed
+    #[arg(long, conflicts_with = "installed")]
+    pub(crate) resolve_from: Option<PathBuf>,
+
+    /// Download the packages of the Cargo.lock file without the crates.io index.
+    ///
+    /// The download url is built from the registry config and each crate is verified
+    /// against the checksum from the Cargo.lock file
+    #[arg(
+        long,
+        requires = "lock_file",
+        conflicts_with_all(["update_index", "resolve_from", "crate_name", "crates", "from_list", "cargo_file", "cargo_metadata", "crate_file", "python_sdist", "scan", "installed"])
+    )]
+    pub(crate) no_index: bool,
+
+    /// Command line of external index provider to take the crates from instead of crates.io.
+    ///
+    /// The provider is asked for the versions of each crate with JSON lines over stdin/stdout
+    /// and returns the index entries with the download url of each version (see README)
+    #[arg(
+        long,
+        conflicts_with_all(["registry", "no_index", "resolve_from"])
+    )]
+    pub(crate) index_provider: Option<String>,
+
+    /// Collect only the platform specific dependencies of the target triple (e.g. x86_64-unknown-linux-gnu),
+    /// can be used multiple times.
+    ///
+    /// The dependencies of all platforms are collected by default
+    #[arg(long, global = true)]
+    pub(crate) target: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Collect the crates that `cargo install` needs for installing binary crate offline
+    Install(InstallArgs),
+
+    /// Print why the package is in Cargo.lock, the chain of dependencies from each local package to it
+    Why(WhyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct WhyArgs {
+    /// The package to explain as `name` or `name@version_req`
+    #[arg(value_name = "CRATE[@VERSION_REQ]")]
+    pub(crate) krate: CrateSpec,
+
+    /// The Cargo.lock file to take the dependencies from
+    #[arg(long, default_value = "Cargo.lock")]
+    pub(crate) cargo_lock_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallArgs {
+    /// The crate to install as `name` or `name@version_req`
+    #[arg(value_name = "CRATE[@VERSION_REQ]")]
+    pub(crate) krate: CrateSpec,
+
+    /// Collect exactly the packages of the Cargo.lock that is published in the crate archive,
+    /// for `cargo install --locked`.
+    ///
+    /// The crate is downloaded in its own pass for reading its Cargo.lock
+    #[arg(long)]
+    pub(crate) locked: bool,
+}
+
+// Crate to collect from the command line, `name[@version_req]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrateSpec {
+    pub(crate) name: String,
+    pub(crate) version_req: Option<String>,
+    pub(crate) features: Option<Vec<String>>,
+}
+
+impl FromStr for CrateSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, version_req) = match spec.split_once('@') {
+            Some((name, version_req)) => {
+                VersionReq::parse(version_req)
+                    .map_err(|e| format!("Invalid version requirement {} for crate {}: {}", version_req, name, e))?;
+
+                (name, Some(version_req.to_string()))
+            }
+            None => (spec, None),
+        };
+
+        if name.is_empty() {
+            return Err(format!("Missing crate name in {}", spec));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            version_req,
+            features: None,
+        })
+    }
+}
+
+impl Cli {
+    // All the crates from `--crate-name` and the positional crates with their features
+    pub fn crate_specs(&self) -> Result<Vec<CrateSpec>> {
+        let mut specs = self.crate_name
+            .iter()
+            .map(|crate_name| CrateSpec {
+                name: crate_name.clone(),
+                version_req: self.crate_version_req.clone(),
+                features: None,
+            })
+            .chain(self.crates.iter().cloned())
+            .collect_vec();
+
+        let mut features_by_crate: HashMap<String, Vec<String>> = HashMap::new();
+
+        for feature in self.features.iter().map(|feature| feature.trim()).filter(|feature| !feature.is_empty()) {
+            let (crate_name, feature) = match feature.split_once('/') {
+                Some((crate_name, feature)) => (crate_name.to_string(), feature.to_string()),
+                None if specs.len() == 1 => (specs[0].name.clone(), feature.to_string()),
+                None => return Err(anyhow!("Feature {} must be given as `crate/feature` when collecting multiple crates", feature)),
+            };
+
+            features_by_crate.entry(crate_name).or_default().push(feature);
+        }
+
+        for spec in specs.iter_mut() {
+            spec.features = features_by_crate.remove(&spec.name);
+        }
+
+        if let Some(crate_name) = features_by_crate.keys().next() {
+            return Err(anyhow!("Features are given for crate {} that is not collected", crate_name));
+        }
+
+        Ok(specs)
+    }
 }
 
 pub fn get_options() -> Cli {
@@ -100,7 +404,7 @@ pub fn get_options() -> Cli {
     let args = std::env::args().collect_vec();
     let args = if args
         .get(1)
-        .and_then(|a| Some(a == "collect"))
+        .map(|a| a == "collect")
         .unwrap_or(false)
     {
         Cli::parse_from(&args[1..])
@@ -108,7 +412,7 @@ pub fn get_options() -> Cli {
         Cli::parse()
     };
 
-    return args;
+    args
 }
 
 
@@ -120,7 +424,7 @@ mod tests {
 
     #[test]
     fn parse_successfully_for_crate_name() {
-        let result = Cli::try_parse_from(vec![
+        let result = Cli::try_parse_from([
             "collect",
             "--crate-name",
             "serde",
@@ -129,26 +433,336 @@ mod tests {
         assert_eq!(result.crate_name, Some("serde".to_string()));
     }
 
+    #[test]
+    fn parse_successfully_for_multiple_crates() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "serde@^1",
+            "tokio@1.35",
+            "anyhow",
+            "--features",
+            "serde/derive,serde/rc",
+            "-F",
+            "tokio/full",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.crate_specs().expect("Valid crate specs"), vec![
+            CrateSpec {
+                name: "serde".to_string(),
+                version_req: Some("^1".to_string()),
+                features: Some(vec!["derive".to_string(), "rc".to_string()]),
+            },
+            CrateSpec {
+                name: "tokio".to_string(),
+                version_req: Some("1.35".to_string()),
+                features: Some(vec!["full".to_string()]),
+            },
+            CrateSpec {
+                name: "anyhow".to_string(),
+                version_req: None,
+                features: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn features_without_crate_for_single_crate() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--crate-name",
+            "serde",
+            "--features",
+            "derive",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(
+            result.crate_specs().expect("Valid crate specs")[0].features,
+            Some(vec!["derive".to_string()])
+        );
+    }
+
+    #[test]
+    fn fail_for_invalid_crate_specs() {
+        assert_eq!(Cli::try_parse_from(["collect", "serde@not-version"].iter()).is_err(), true);
+        assert_eq!(Cli::try_parse_from(["collect", "@1.0"].iter()).is_err(), true);
+
+        let result = Cli::try_parse_from(["collect", "serde", "tokio", "--features", "derive"].iter())
+            .expect("Valid arguments");
+        assert_eq!(result.crate_specs().is_err(), true);
+
+        let result = Cli::try_parse_from(["collect", "serde", "--features", "tokio/full"].iter())
+            .expect("Valid arguments");
+        assert_eq!(result.crate_specs().is_err(), true);
+    }
+
+    #[test]
+    fn parse_successfully_for_from_list() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--from-list",
+            "./deps.txt",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.from_list, vec![PathBuf::from("./deps.txt")]);
+    }
+
     #[test]
     fn parse_successfully_for_lock_file() {
-        let result = Cli::try_parse_from(vec![
+        let result = Cli::try_parse_from([
             "collect",
             "--cargo-lock-file",
             "./Cargo.lock",
         ].iter()).expect("Valid arguments");
 
-        assert_eq!(result.cargo_lock_file, Some("./Cargo.lock".to_string()));
+        assert_eq!(result.cargo_lock_file, vec!["./Cargo.lock".to_string()]);
     }
 
     #[test]
     fn parse_successfully_for_cargo_file() {
-        let result = Cli::try_parse_from(vec![
+        let result = Cli::try_parse_from([
             "collect",
             "--cargo-file",
             "./Cargo.toml",
         ].iter()).expect("Valid arguments");
 
-        assert_eq!(result.cargo_file, Some("./Cargo.toml".to_string()));
+        assert_eq!(result.cargo_file, vec!["./Cargo.toml".to_string()]);
+    }
+
+    #[test]
+    fn parse_successfully_for_cargo_metadata() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--cargo-metadata",
+            "metadata.json",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.cargo_metadata, vec![PathBuf::from("metadata.json")]);
+    }
+
+    #[test]
+    fn parse_successfully_for_build_std() {
+        let result = Cli::try_parse_from(["collect", "--build-std"].iter()).expect("Valid arguments");
+        assert_eq!(result.build_std, Some(None));
+
+        let result = Cli::try_parse_from(["collect", "--build-std", "nightly", "--no-index"].iter()).expect("Valid arguments");
+        assert_eq!(result.build_std, Some(Some("nightly".to_string())));
+        assert_eq!(result.no_index, true);
+    }
+
+    #[test]
+    fn parse_successfully_for_crate_file() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--crate-file",
+            "foo-1.2.3.crate",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.crate_file, vec![PathBuf::from("foo-1.2.3.crate")]);
+    }
+
+    #[test]
+    fn parse_successfully_for_python_sdist() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--python-sdist",
+            "cryptography-42.0.0.tar.gz",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.python_sdist, vec![PathBuf::from("cryptography-42.0.0.tar.gz")]);
+    }
+
+    #[test]
+    fn parse_successfully_for_sbom() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--sbom",
+            "product.cdx.json",
+            "--no-index",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.sbom, vec![PathBuf::from("product.cdx.json")]);
+        assert_eq!(result.no_index, true);
+    }
+
+    #[test]
+    fn parse_successfully_for_installed() {
+        let result = Cli::try_parse_from(["collect", "--installed"].iter()).expect("Valid arguments");
+
+        assert_eq!(result.installed, true);
+        assert_eq!(Cli::try_parse_from(["collect", "--installed", "--no-index"].iter()).is_err(), true);
+        assert_eq!(Cli::try_parse_from(["collect", "--installed", "--resolve-from", "./deps"].iter()).is_err(), true);
+    }
+
+    #[test]
+    fn parse_successfully_for_install() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "install",
+            "ripgrep@14",
+            "--locked",
+            "--output",
+            "tools",
+        ].iter()).expect("Valid arguments");
+
+        let Some(Command::Install(install)) = result.command else {
+            panic!("Expected install command");
+        };
+
+        assert_eq!(install.krate, CrateSpec {
+            name: "ripgrep".to_string(),
+            version_req: Some("14".to_string()),
+            features: None,
+        });
+        assert_eq!(install.locked, true);
+        assert_eq!(result.output, PathBuf::from("tools"));
+        assert_eq!(result.crates, vec![]);
+    }
+
+    #[test]
+    fn parse_successfully_for_mixed_inputs() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "serde@^1",
+            "--build-std",
+            "--cargo-lock-file",
+            "app/Cargo.lock",
+            "--cargo-lock-file",
+            "tool/Cargo.lock",
+            "--cargo-file",
+            "lib/Cargo.toml",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.crates.len(), 1);
+        assert_eq!(result.build_std, Some(None));
+        assert_eq!(result.cargo_lock_file, vec!["app/Cargo.lock".to_string(), "tool/Cargo.lock".to_string()]);
+        assert_eq!(result.cargo_file, vec!["lib/Cargo.toml".to_string()]);
+    }
+
+    #[test]
+    fn parse_successfully_for_scan() {
+        let result = Cli::try_parse_from(["collect", "--scan", "projects", "--scan", "tools"].iter()).expect("Valid arguments");
+
+        assert_eq!(result.scan, vec![PathBuf::from("projects"), PathBuf::from("tools")]);
+    }
+
+    #[test]
+    fn parse_successfully_for_workspace_members() {
+        let result = Cli::try_parse_from(["collect", "--cargo-file", "Cargo.toml", "-p", "cli", "--package", "server"].iter()).expect("Valid arguments");
+
+        assert_eq!(result.package, vec!["cli".to_string(), "server".to_string()]);
+
+        let result = Cli::try_parse_from(["collect", "--cargo-lock-file", "Cargo.lock", "-p", "cli"].iter()).expect("Valid arguments");
+
+        assert_eq!(result.package, vec!["cli".to_string()]);
+        assert_eq!(Cli::try_parse_from(["collect", "--scan", "projects", "-p", "cli"].iter()).is_err(), true);
+    }
+
+    #[test]
+    fn parse_successfully_for_why() {
+        let result = Cli::try_parse_from(["collect", "why", "serde@0.9"].iter()).expect("Valid arguments");
+
+        match result.command {
+            Some(Command::Why(why)) => {
+                assert_eq!(why.krate.name, "serde");
+                assert_eq!(why.krate.version_req, Some("0.9".to_string()));
+                assert_eq!(why.cargo_lock_file, PathBuf::from("Cargo.lock"));
+            }
+            _ => panic!("Expected why subcommand"),
+        }
+    }
+
+    #[test]
+    fn fail_without_input() {
+        assert_eq!(Cli::try_parse_from(["collect", "--output", "deps"].iter()).is_err(), true);
+    }
+
+    #[test]
+    fn parse_successfully_for_resolve_from() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--cargo-lock-file",
+            "./Cargo.lock",
+            "--resolve-from",
+            "./deps",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.resolve_from, Some(PathBuf::from("./deps")));
+    }
+
+    #[test]
+    fn parse_successfully_for_registry() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--crate-name",
+            "my-crate",
+            "--registry",
+            "my-registry",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.registry, Some("my-registry".to_string()));
+    }
+
+    #[test]
+    fn parse_successfully_for_no_index() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--cargo-lock-file",
+            "./Cargo.lock",
+            "--no-index",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.no_index, true);
+    }
+
+    #[test]
+    fn parse_successfully_for_index_provider() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--crate-name",
+            "my-crate",
+            "--index-provider",
+            "./my-provider --flag",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.index_provider, Some("./my-provider --flag".to_string()));
+    }
+
+    #[test]
+    fn parse_successfully_for_multiple_targets() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--cargo-file",
+            "./Cargo.toml",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--target",
+            "x86_64-pc-windows-msvc",
+        ].iter()).expect("Valid arguments");
+
+        assert_eq!(result.target, vec!["x86_64-unknown-linux-gnu".to_string(), "x86_64-pc-windows-msvc".to_string()]);
+    }
+
+    #[test]
+    fn fail_for_no_index_without_lock_file() {
+        let result = Cli::try_parse_from([
+            "collect",
+            "--crate-name",
+            "serde",
+            "--no-index",
+        ].iter());
+
+        assert_eq!(result.is_err(), true);
+
+        let result = Cli::try_parse_from([
+            "collect",
+            "--cargo-lock-file",
+            "Cargo.lock",
+            "--cargo-file",
+            "Cargo.toml",
+            "--no-index",
+        ].iter());
+
+        assert_eq!(result.is_err(), true);
     }
 }
 
@@ -1,43 +1,74 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use crates_index::{GitIndex, IndexConfig};
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::ProgressBar;
 use itertools::Itertools;
+use sha2::{Digest, Sha256};
 use tracing::{info, warn};
 use semver::{Version as SemVersion, VersionReq};
-use crate::CratesToDownload;
+use crate::{CrateToDownload, CratesToDownload, Patches};
+use crate::registry::Registries;
 use crate::spinners::progress_spinner;
+use crate::target_filter::TargetFilter;
 
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub struct Package {
     pub(crate) path: PathBuf,
     pub(crate) url: String,
     pub(crate) checksum: Vec<u8>,
+    // Token for the `Authorization` header when the registry requires authentication
+    pub(crate) auth_token: Option<String>,
 }
 
 impl Package {
-    pub fn new(path: PathBuf, url: String, checksum: Vec<u8>) -> Self {
+    pub fn new(path: PathBuf, url: String, checksum: Vec<u8>, auth_token: Option<String>) -> Self {
         Self {
             path,
             url,
             checksum,
+            auth_token,
         }
     }
 }
 
+// Registry package from Cargo.lock with its exact version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    // Index url of the registry from the package source
+    pub(crate) registry: String,
+    pub(crate) checksum: Option<String>,
+}
+
+// Crate versions with the patches they were reached with, the dependencies are collected once for each patches
+type ExpandedCrates = HashSet<(String, String, Patches)>;
+
 async fn find_highest_requirement_version(
-    index: &GitIndex,
-    index_config: &IndexConfig,
+    registries: &mut Registries,
     packages: &mut HashSet<Package>,
+    expanded: &mut ExpandedCrates,
     folder_path: &Path,
-    crate_name: &str,
-    crate_version_req: &str,
+    crate_to_download: &CrateToDownload,
+    targets: &TargetFilter,
     pb: &ProgressBar,
-) -> Result<(Option<String>, Vec<(String, String)>)> {
+) -> Result<(Option<String>, CratesToDownload)> {
+    let crate_name = crate_to_download.name.as_str();
+    let crate_version_req = crate_to_download.version_req.as_str();
+
     pb.set_message(crate_name.to_owned());
-    let krate = index
-        .crate_(crate_name);
+    let default_index_url = registries.default_registry().index_url.clone();
+    let registry = registries
+        .get(crate_to_download.registry.as_deref())
+        .await?;
+    let krate = registry
+        .crate_(crate_name)
+        .await?;
+
+    // The index provider replaces crates.io, crates that it does not have are not taken from crates.io behind its back
+    if krate.is_none() && registry.is_index_provider() {
+        return Err(anyhow!("Crate {} was not found by index provider {}", crate_name, registry.name));
+    }
 
     if krate.is_none() {
         warn!("Crate {} not found, skipping", crate_name);
@@ -73,25 +104,67 @@ async fn find_highest_requirement_version(
     let version = versions
         .iter()
         .find(|(v, _)| !v.is_yanked())
-        .or(versions.get(0));
+        .or(versions.first());
 
     if let Some((version, _)) = version {
-        let url = version
-            .download_url(index_config)
+        if let Some(locked_checksum) = &crate_to_download.checksum {
+            let index_checksum = hex::encode(version.checksum());
+
+            if !index_checksum.eq_ignore_ascii_case(locked_checksum) {
+                return Err(anyhow!(
+                    "Checksum mismatch for crate {} {}: Cargo.lock has {} but the index {} has {}, the index or the lock file may have been tampered with",
+                    crate_name,
+                    version.version(),
+                    locked_checksum,
+                    registry.index_url,
+                    index_checksum
+                ));
+            }
+        }
+
+        let url = registry
+            .download_url(crate_name, version.version())
             .ok_or_else(|| anyhow!("Can't generate download url for crate: {}", crate_name))?;
         let pkg = Package::new(
             folder_path.join(format!("{}-{}.crate", crate_name, version.version())),
             url,
             version.checksum().to_vec(),
+            registry.auth_token().map(|token| token.to_string()),
         );
 
-        // If the package already processed skip their dependencies.
+        // Crate with requested features needs only the optional dependencies of the enabled features
+        let enabled_optional_dependencies = crate_to_download.features
+            .as_ref()
+            .map(|features| get_enabled_optional_dependencies(version.features(), features, crate_to_download.default_features));
+
+        // Dependency without registry is taken from the same registry as the crate, otherwise from the registry of its index url
+        let dependency_registry = |dep: &crates_index::Dependency| match dep.registry() {
+            Some(index_url) => Some(index_url.to_string()),
+            None if registry.index_url == default_index_url => None,
+            None => Some(registry.index_url.clone()),
+        };
+
         if packages.insert(pkg) {
             pb.inc(1);
+        }
+
+        // If the package already processed with the same patches skip their dependencies.
+        if expanded.insert((crate_name.to_string(), version.version().to_string(), crate_to_download.patches.clone())) {
             Ok((Some(version.version().to_string()), version
                 .dependencies()
-                .into_iter()
-                .map(|dep| (dep.crate_name().to_owned(), dep.requirement().to_owned()))
+                .iter()
+                // Skip dependencies of other platforms
+                .filter(|dep| dep.target().is_none_or(|target| targets.matches(target)))
+                .filter(|dep| {
+                    !dep.is_optional() || enabled_optional_dependencies
+                        .as_ref()
+                        .is_none_or(|enabled| enabled.contains(dep.name()))
+                })
+                .map(|dep| {
+                    CrateToDownload::new(dep.crate_name(), dep.requirement())
+                        .with_registry(dependency_registry(dep))
+                        .with_patches(crate_to_download.patches.clone())
+                })
                 .collect_vec()))
         } else {
             Ok((None, vec![]))
@@ -115,33 +188,58 @@ async fn find_highest_requirement_version(
 }
 
 pub async fn collect_packages(
-    index: &GitIndex,
+    registries: &mut Registries,
     worklist: &mut CratesToDownload,
     output: &Path,
+    targets: &TargetFilter,
 ) -> Result<HashSet<Package>> {
     // Collect all dependencies recursively.
     let mut packages = HashSet::new();
-    let index_config = index.index_config()?;
     let pb = progress_spinner()?;
     info!("Collect dependencies recursively...");
 
-    let mut already_downloaded = build_hashset_from_local_deps(output.to_str().unwrap().to_string());
-    while let Some((crate_name, crate_version_req)) = worklist.pop() {
-        if already_downloaded.contains_key(&crate_name) {
-            let versions = already_downloaded.get(&crate_name).unwrap();
-            let matched = versions.iter().find(|v| is_version_match_the_range(v.as_str().to_string(), crate_version_req.clone()));
-            if matched.is_some() {
-                continue;
+    let local_crates = build_hashset_from_local_deps(output.to_str().unwrap().to_string());
+    let mut already_downloaded = local_crates.clone();
+    let mut expanded = ExpandedCrates::new();
+    while let Some(crate_to_download) = worklist.pop() {
+        // The dependencies of the replacement are collected instead
+        if crate_to_download.is_patched() {
+            continue;
+        }
+
+        if already_downloaded.contains_key(&crate_to_download.name) {
+            let versions = already_downloaded.get(&crate_to_download.name).unwrap();
+            let matched = versions.iter().find(|v| is_version_match_the_range(v.as_str().to_string(), crate_to_download.version_req.clone()));
+            if let Some(matched) = matched {
+                let is_local = local_crates
+                    .get(&crate_to_download.name)
+                    .is_some_and(|versions| versions.contains(matched));
+
+                match &crate_to_download.checksum {
+                    // Crate from previous collection is verified against Cargo.lock
+                    Some(checksum) if is_local => {
+                        verify_crate_file_checksum(output.join(format!("{}-{}.crate", crate_to_download.name, matched)).as_path(), checksum)?;
+                    }
+                    // Crate that was collected in this run is compared with the index again
+                    Some(_) => {}
+                    // Crate that was reached with other patches is expanded again, it may depend on crates that they replace
+                    None if !is_local && !expanded.contains(&(crate_to_download.name.clone(), matched.clone(), crate_to_download.patches.clone())) => {}
+                    None => continue,
+                }
+
+                if is_local {
+                    continue;
+                }
             }
         }
 
         let (version, deps) = find_highest_requirement_version(
-            &index,
-            &index_config,
+            registries,
             &mut packages,
+            &mut expanded,
             output,
-            &crate_name,
-            &crate_version_req,
+            &crate_to_download,
+            targets,
             &pb,
         )
             .await?;
@@ -154,20 +252,135 @@ pub async fn collect_packages(
 
         let version = version.unwrap();
 
-        if already_downloaded.contains_key(&crate_name) {
-            let versions = already_downloaded.get_mut(&crate_name).unwrap();
-            if !versions.contains(&version) {
-                versions.insert(version);
+        already_downloaded
+            .entry(crate_to_download.name)
+            .or_default()
+            .insert(version);
+    }
+    Ok(packages)
+}
+
+// The package of the highest version that matches the requirement, without collecting its dependencies
+pub async fn find_package(
+    registries: &mut Registries,
+    crate_to_download: &CrateToDownload,
+    output: &Path,
+) -> Result<Option<(String, Package)>> {
+    let mut packages = HashSet::new();
+
+    let (version, _) = find_highest_requirement_version(
+        registries,
+        &mut packages,
+        &mut ExpandedCrates::new(),
+        output,
+        crate_to_download,
+        &TargetFilter::default(),
+        &ProgressBar::hidden(),
+    )
+        .await?;
+
+    Ok(version.zip(packages.into_iter().next()))
+}
+
+// Names of the optional dependencies that the given features, and the default features unless disabled, enable
+fn get_enabled_optional_dependencies(features: &HashMap<String, Vec<String>>, requested_features: &[String], default_features: bool) -> HashSet<String> {
+    let mut enabled_features = HashSet::new();
+    let mut enabled_dependencies = HashSet::new();
+
+    let mut worklist = requested_features.to_vec();
+    if default_features {
+        worklist.push("default".to_string());
+    }
+
+    while let Some(feature) = worklist.pop() {
+        if !enabled_features.insert(feature.clone()) {
+            continue;
+        }
+
+        for value in features.get(&feature).into_iter().flatten() {
+            if let Some(dependency) = value.strip_prefix("dep:") {
+                enabled_dependencies.insert(dependency.to_string());
+            } else if let Some((dependency, _)) = value.split_once('/') {
+                // `dependency?/feature` does not enable the dependency
+                if !dependency.ends_with('?') {
+                    enabled_dependencies.insert(dependency.to_string());
+                }
+            } else {
+                worklist.push(value.clone());
             }
-        } else {
-            let mut versions = HashSet::new();
-            versions.insert(version);
-            already_downloaded.insert(crate_name, versions);
         }
+
+        // Optional dependency without `dep:` is also implicit feature with its name
+        enabled_dependencies.insert(feature);
+    }
+
+    enabled_dependencies
+}
+
+// Build the packages directly from Cargo.lock without reading the index,
+// the lock file already has the exact version and checksum of every dependency
+pub async fn collect_locked_packages(
+    registries: &mut Registries,
+    locked_packages: &[LockedPackage],
+    output: &Path,
+) -> Result<HashSet<Package>> {
+    let mut packages = HashSet::new();
+    info!("Collect locked packages without index...");
+
+    let already_downloaded = build_hashset_from_local_deps(output.to_str().unwrap().to_string());
+    for locked_package in locked_packages {
+        let name = locked_package.name.as_str();
+        let version = locked_package.version.as_str();
+
+        let checksum = locked_package
+            .checksum
+            .as_deref()
+            .ok_or_else(|| anyhow!("Package {} {} does not have checksum, it is required without index", name, version))?;
+
+        if already_downloaded.get(name).is_some_and(|versions| versions.contains(version)) {
+            verify_crate_file_checksum(output.join(format!("{}-{}.crate", name, version)).as_path(), checksum)?;
+            continue;
+        }
+
+        let checksum = hex::decode(checksum)
+            .with_context(|| format!("Invalid checksum {} for package {} {} in Cargo.lock", checksum, name, version))?;
+
+        let registry = registries
+            .get(Some(locked_package.registry.as_str()))
+            .await?;
+        let url = registry
+            .download_url(name, version)
+            .ok_or_else(|| anyhow!("Can't generate download url for crate: {}", name))?;
+
+        packages.insert(Package::new(
+            output.join(format!("{}-{}.crate", name, version)),
+            url,
+            checksum,
+            registry.auth_token().map(|token| token.to_string()),
+        ));
     }
+
     Ok(packages)
 }
 
+// Verify crate file from previous collection against the checksum from Cargo.lock
+fn verify_crate_file_checksum(path: &Path, checksum: &str) -> Result<()> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("Failed to read crate file at {}", path.display()))?;
+    let file_checksum = hex::encode(Sha256::digest(&content));
+
+    if !file_checksum.eq_ignore_ascii_case(checksum) {
+        return Err(anyhow!(
+            "Checksum mismatch for crate file {}: Cargo.lock has {} but the file has {}",
+            path.display(),
+            checksum,
+            file_checksum
+        ));
+    }
+
+    Ok(())
+}
+
 fn is_version_match_the_range(version: String, range: String) -> bool {
     let version_req = VersionReq::parse(range.as_str());
 
@@ -185,30 +398,21 @@ fn is_version_match_the_range(version: String, range: String) -> bool {
         SemVersion::new(0, 0, 0)
     });
 
-    return version_req.matches(&semversion);
-}
-
-fn create_file_name_from_crate_name_and_version(crate_name: String, version: String) -> String {
-    return format!("{}-{}.crate", crate_name, version);
+    version_req.matches(&semversion)
 }
 
+// The version starts at the first `-` that is followed by a valid version, names and prerelease versions can have `-` as well
 fn parse_crate_name_and_version_from_file_name(file_name: &str) -> Option<(String, String)> {
-    let crate_and_version = file_name.replace(".crate", "");
-
-    let position = crate_and_version.rfind("-");
-
-    if position.is_none() {
-        return None;
-    }
-
-    let position = position.unwrap();
+    let crate_and_version = file_name.strip_suffix(".crate")?;
 
-    let crate_name = &crate_and_version[0..position];
-    let version = &crate_and_version[position + 1..crate_and_version.len()];
-
-    return Some((crate_name.to_string(), version.to_string()));
+    crate_and_version
+        .match_indices('-')
+        .map(|(position, _)| (&crate_and_version[..position], &crate_and_version[position + 1..]))
+        .find(|(_, version)| SemVersion::parse(version).is_ok())
+        .map(|(crate_name, version)| (crate_name.to_string(), version.to_string()))
 }
 
+// Only the registry crates at the top of the folder, the packaged git crates in its `git` folder are not registry crates
 fn build_hashset_from_local_deps(folder_with_already_download: String) -> HashMap<String, HashSet<String>> {
     let mut map: HashMap<String, HashSet<String>> = HashMap::new();
 
@@ -218,39 +422,297 @@ fn build_hashset_from_local_deps(folder_with_already_download: String) -> HashMa
 
         .filter_map(|entry| {
             let path = entry.path();
-            let file_name = path.file_name();
-
-            if file_name.is_none() {
-                return None;
-            }
-
-            let file_name = file_name.unwrap().to_str();
-
-            if file_name.is_none() {
-                return None;
-            }
-
-            let file_name = file_name.unwrap().to_string();
+            let file_name = path.file_name()?.to_str()?.to_string();
 
             if !file_name.ends_with(".crate") {
                 return None;
             }
 
-            return parse_crate_name_and_version_from_file_name(file_name.as_str());
+            parse_crate_name_and_version_from_file_name(file_name.as_str())
         })
 
         .for_each(|(crate_name, version)| {
-            if map.contains_key(&crate_name) {
-                let versions: &mut HashSet<String> = map.get_mut(&crate_name).unwrap();
-                if !versions.contains(&version) {
-                    (versions).insert(version.to_string());
-                }
-            } else {
-                let mut versions = HashSet::new();
-                versions.insert(version.to_string());
-                map.insert(crate_name.to_string(), versions);
-            }
+            map.entry(crate_name).or_default().insert(version);
         });
 
-    return map;
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::parse_cargo_files::package_source::{CRATES_IO_INDEX_URL, CRATES_IO_SPARSE_INDEX_URL};
+    use crate::registry::{Registry, RegistryConfig};
+
+    use super::*;
+
+    fn crates_io_registries() -> Registries {
+        Registries::new(Registry::with_config("crates-io", CRATES_IO_SPARSE_INDEX_URL, RegistryConfig {
+            dl: "https://static.crates.io/crates".to_string(),
+            api: None,
+            auth_required: false,
+        }))
+    }
+
+    fn locked_package(name: &str, version: &str, checksum: Option<&str>) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            registry: CRATES_IO_INDEX_URL.to_string(),
+            checksum: checksum.map(|checksum| checksum.to_string()),
+        }
+    }
+
+    #[test]
+    fn parse_name_and_version_of_crate_file() {
+        let parse = |file_name: &str| parse_crate_name_and_version_from_file_name(file_name);
+        let name_and_version = |name: &str, version: &str| Some((name.to_string(), version.to_string()));
+
+        assert_eq!(parse("serde-1.0.188.crate"), name_and_version("serde", "1.0.188"));
+        assert_eq!(parse("foo-1.0.0-beta.1.crate"), name_and_version("foo", "1.0.0-beta.1"));
+        assert_eq!(parse("foo-bar-2d-0.3.0-rc-1.crate"), name_and_version("foo-bar-2d", "0.3.0-rc-1"));
+        assert_eq!(parse("not-a-crate.crate"), None);
+        assert_eq!(parse("serde-1.0.188.tar.gz"), None);
+    }
+
+    #[test]
+    fn enabled_optional_dependencies_by_features() {
+        let features: HashMap<String, Vec<String>> = [
+            ("default", vec!["std"]),
+            ("std", vec!["dep:libc", "serde?/std"]),
+            ("derive", vec!["serde_derive"]),
+            ("serde_derive", vec!["dep:serde_derive"]),
+            ("full", vec!["derive", "tokio/full"]),
+        ]
+            .into_iter()
+            .map(|(feature, values)| (feature.to_string(), values.into_iter().map(|value| value.to_string()).collect()))
+            .collect();
+
+        let enabled = get_enabled_optional_dependencies(&features, &[], true);
+        assert_eq!(enabled.contains("libc"), true);
+        assert_eq!(enabled.contains("serde"), false);
+        assert_eq!(enabled.contains("serde_derive"), false);
+
+        let enabled = get_enabled_optional_dependencies(&features, &["full".to_string(), "serde".to_string()], true);
+        assert_eq!(enabled.contains("serde_derive"), true);
+        assert_eq!(enabled.contains("tokio"), true);
+        // Implicit feature of optional dependency
+        assert_eq!(enabled.contains("serde"), true);
+
+        // Like `--no-default-features`
+        let enabled = get_enabled_optional_dependencies(&features, &["derive".to_string()], false);
+        assert_eq!(enabled.contains("libc"), false);
+        assert_eq!(enabled.contains("serde_derive"), true);
+    }
+
+    #[tokio::test]
+    async fn collect_locked_packages_without_index() {
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        fs::write(tmp_dir.path().join("itoa-1.0.9.crate"), "itoa").expect("Failed to write file");
+        let itoa_checksum = hex::encode(Sha256::digest("itoa"));
+
+        let mut registries = crates_io_registries();
+        let packages = collect_locked_packages(&mut registries, &[
+            locked_package("serde", "1.0.188", Some("cf9e0fcba69a370eed61bcf2b728575f726b50b55cba78064753d708ddc7549e")),
+            locked_package("itoa", "1.0.9", Some(itoa_checksum.as_str())),
+        ], tmp_dir.path()).await.expect("Collect successfully");
+
+        assert_eq!(packages, HashSet::from([
+            Package::new(
+                tmp_dir.path().join("serde-1.0.188.crate"),
+                "https://static.crates.io/crates/serde/1.0.188/download".to_string(),
+                hex::decode("cf9e0fcba69a370eed61bcf2b728575f726b50b55cba78064753d708ddc7549e").unwrap(),
+                None,
+            ),
+        ]));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn collect_packages_from_index_provider() {
+        use crate::registry::test_utils::{write_fake_index_provider, FAKE_PROVIDER_CHECKSUM};
+
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir(&output).expect("Failed to create dir");
+
+        let mut registries = Registries::new(
+            Registry::from_index_provider(provider_path.to_str().unwrap()).expect("Provider started")
+        );
+        let packages = collect_packages(&mut registries, &mut vec![CrateToDownload::new("foo", "^1")], &output, &TargetFilter::default())
+            .await
+            .expect("Collect successfully");
+
+        let checksum = hex::decode(FAKE_PROVIDER_CHECKSUM).unwrap();
+        assert_eq!(packages, HashSet::from([
+            Package::new(output.join("foo-1.1.0.crate"), "https://store.local/foo/1.1.0.crate".to_string(), checksum.clone(), None),
+            Package::new(output.join("bar-0.1.2.crate"), "https://store.local/bar/0.1.2.crate".to_string(), checksum, None),
+        ]));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn skip_patched_dependencies() {
+        use crate::registry::test_utils::{write_fake_index_provider, FAKE_PROVIDER_CHECKSUM};
+        use crate::PatchedCrate;
+
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir(&output).expect("Failed to create dir");
+
+        let mut registries = Registries::new(
+            Registry::from_index_provider(provider_path.to_str().unwrap()).expect("Provider started")
+        );
+
+        // The manifest depends on `foo` and patches `bar`, that `foo` depends on
+        let patches = Patches::new(std::collections::BTreeSet::from([PatchedCrate { registry: None, name: "bar".to_string() }]));
+        let patched_foo = CrateToDownload::new("foo", "^1").with_patches(patches);
+
+        let package = |name: &str, version: &str| Package::new(
+            output.join(format!("{}-{}.crate", name, version)),
+            format!("https://store.local/{}/{}.crate", name, version),
+            hex::decode(FAKE_PROVIDER_CHECKSUM).unwrap(),
+            None,
+        );
+
+        let packages = collect_packages(&mut registries, &mut vec![patched_foo.clone()], &output, &TargetFilter::default())
+            .await
+            .expect("Collect successfully");
+
+        assert_eq!(packages, HashSet::from([package("foo", "1.1.0")]));
+
+        // Other input without the patch still collects `bar`
+        let packages = collect_packages(&mut registries, &mut vec![patched_foo, CrateToDownload::new("foo", "^1")], &output, &TargetFilter::default())
+            .await
+            .expect("Collect successfully");
+
+        assert_eq!(packages, HashSet::from([package("foo", "1.1.0"), package("bar", "0.1.2")]));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn route_dependencies_to_their_registry() {
+        use crate::registry::test_utils::write_fake_index_provider;
+
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+
+        // Private registry next to crates.io
+        let private_registry = Registry::from_index_provider(provider_path.to_str().unwrap()).expect("Provider started");
+        let private_index_url = private_registry.index_url.clone();
+
+        let mut registries = crates_io_registries();
+        registries.insert(private_registry);
+
+        let (version, deps) = find_highest_requirement_version(
+            &mut registries,
+            &mut HashSet::new(),
+            &mut ExpandedCrates::new(),
+            tmp_dir.path(),
+            &CrateToDownload::new("private", "^0.2").with_registry(Some(private_index_url.clone())),
+            &TargetFilter::default(),
+            &ProgressBar::hidden(),
+        )
+            .await
+            .expect("Find successfully");
+
+        assert_eq!(version, Some("0.2.0".to_string()));
+        // `serde` is taken from crates.io and never looked up by name in the private registry
+        assert_eq!(
+            deps.iter().map(|dep| (dep.name.as_str(), dep.registry.as_deref())).sorted().collect_vec(),
+            vec![("bar", Some(private_index_url.as_str())), ("serde", Some(CRATES_IO_INDEX_URL))]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn take_crates_io_packages_only_from_index_provider() {
+        use crate::registry::test_utils::{write_fake_index_provider, FAKE_PROVIDER_CHECKSUM};
+
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir(&output).expect("Failed to create dir");
+
+        let mut registries = Registries::new(
+            Registry::from_index_provider(provider_path.to_str().unwrap()).expect("Provider started")
+        );
+
+        // Package from Cargo.lock with crates.io source
+        let locked = CrateToDownload::new("bar", "=0.1.2").with_registry(Some(CRATES_IO_INDEX_URL.to_string()));
+        let packages = collect_packages(&mut registries, &mut vec![locked], &output, &TargetFilter::default())
+            .await
+            .expect("Collect successfully");
+
+        assert_eq!(packages, HashSet::from([
+            Package::new(output.join("bar-0.1.2.crate"), "https://store.local/bar/0.1.2.crate".to_string(), hex::decode(FAKE_PROVIDER_CHECKSUM).unwrap(), None),
+        ]));
+
+        let result = collect_packages(&mut registries, &mut vec![CrateToDownload::new("not-exists", "*")], &output, &TargetFilter::default()).await;
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[tokio::test]
+    async fn fail_for_locked_package_without_checksum() {
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+
+        let mut registries = crates_io_registries();
+        let result = collect_locked_packages(&mut registries, &[
+            locked_package("serde", "1.0.188", None),
+        ], tmp_dir.path()).await;
+
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[tokio::test]
+    async fn fail_for_local_crate_file_with_other_checksum() {
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        fs::write(tmp_dir.path().join("itoa-1.0.9.crate"), "tampered").expect("Failed to write file");
+
+        let mut registries = crates_io_registries();
+        let result = collect_locked_packages(&mut registries, &[
+            locked_package("itoa", "1.0.9", Some("af150ab688ff2122fcef229be89cb50dd66af9e01a4ff320cc137eecc9bacc38")),
+        ], tmp_dir.path()).await;
+
+        assert_eq!(result.is_err(), true);
+
+        let result = collect_packages(
+            &mut registries,
+            &mut vec![CrateToDownload::new("itoa", "=1.0.9").with_checksum(Some("af150ab688ff2122fcef229be89cb50dd66af9e01a4ff320cc137eecc9bacc38".to_string()))],
+            tmp_dir.path(),
+            &TargetFilter::default(),
+        ).await;
+
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn fail_for_lock_file_checksum_that_differs_from_index() {
+        use crate::registry::test_utils::{write_fake_index_provider, FAKE_PROVIDER_CHECKSUM};
+
+        let tmp_dir = tempdir::TempDir::new("collect_packages_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir(&output).expect("Failed to create dir");
+
+        let mut registries = Registries::new(
+            Registry::from_index_provider(provider_path.to_str().unwrap()).expect("Provider started")
+        );
+
+        let locked = |checksum: &str| vec![CrateToDownload::new("bar", "=0.1.2").with_checksum(Some(checksum.to_string()))];
+
+        let packages = collect_packages(&mut registries, &mut locked(FAKE_PROVIDER_CHECKSUM), &output, &TargetFilter::default())
+            .await
+            .expect("Collect successfully");
+        assert_eq!(packages.len(), 1);
+
+        let result = collect_packages(&mut registries, &mut locked(&"0".repeat(64)), &output, &TargetFilter::default()).await;
+        assert_eq!(result.is_err(), true);
+    }
 }
@@ -0,0 +1,212 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use itertools::Itertools;
+use tar::Archive;
+
+// Folders that are never part of the package
+const IGNORED_PACKAGE_FOLDERS: [&str; 2] = [".git", "target"];
+
+// Read a file from a `.crate` archive.
+// The file path is relative to the package root (crate archives put everything under `<name>-<version>/`)
+pub fn read_file_from_crate_archive(crate_file_path: &Path, file_path_in_package: &str) -> Result<Option<String>> {
+    let crate_file = File::open(crate_file_path)
+        .with_context(|| format!("Failed to open crate file at {}", crate_file_path.display()))?;
+
+    let mut archive = Archive::new(GzDecoder::new(crate_file));
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        // Skip the `<name>-<version>` folder
+        let path_in_package = entry.path()?
+            .components()
+            .skip(1)
+            .collect::<PathBuf>();
+
+        if path_in_package != Path::new(file_path_in_package) {
+            continue;
+        }
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content)
+            .with_context(|| format!("Failed to read {} from {}", file_path_in_package, crate_file_path.display()))?;
+
+        return Ok(Some(content));
+    }
+
+    Ok(None)
+}
+
+// Extract `.tar.gz` archive (crate file or python sdist) into the folder
+pub fn unpack_archive(archive_path: &Path, folder: &Path) -> Result<()> {
+    let archive_file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive at {}", archive_path.display()))?;
+
+    Archive::new(GzDecoder::new(archive_file))
+        .unpack(folder)
+        .with_context(|| format!("Failed to extract {} to {}", archive_path.display(), folder.display()))
+}
+
+// Create `.crate` archive from the package folder, the files are placed under `<name>-<version>/`.
+// Like `cargo package`, the Cargo.toml is replaced by the normalized one and the original is kept as `Cargo.toml.orig`
+pub fn write_crate_archive_from_folder(package_folder: &Path, name: &str, version: &str, cargo_file_content: &str, crate_file_path: &Path) -> Result<()> {
+    let crate_file = File::create(crate_file_path)
+        .with_context(|| format!("Failed to create crate file at {}", crate_file_path.display()))?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(crate_file, Compression::default()));
+    let package_path_in_archive = PathBuf::from(format!("{}-{}", name, version));
+
+    append_folder_to_archive(&mut builder, package_folder, package_path_in_archive.as_path(), true)?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(cargo_file_content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    builder.append_data(&mut header, package_path_in_archive.join("Cargo.toml"), cargo_file_content.as_bytes())
+        .with_context(|| format!("Failed to add Cargo.toml to crate archive {}", crate_file_path.display()))?;
+
+    builder.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+fn append_folder_to_archive<W: std::io::Write>(builder: &mut tar::Builder<W>, folder: &Path, path_in_archive: &Path, is_package_root: bool) -> Result<()> {
+    // Sorted for reproducible archives
+    let entries = fs::read_dir(folder)?
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .sorted_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_name = entry.file_name();
+        let entry_path_in_archive = path_in_archive.join(&file_name);
+
+        if entry.file_type()?.is_dir() {
+            if IGNORED_PACKAGE_FOLDERS.iter().any(|ignored| file_name == *ignored) {
+                continue;
+            }
+
+            // Nested packages (e.g. the members inside the root package of workspace) are packaged on their own
+            if entry.path().join("Cargo.toml").is_file() {
+                continue;
+            }
+
+            append_folder_to_archive(builder, entry.path().as_path(), entry_path_in_archive.as_path(), false)?;
+        } else if is_package_root && file_name == "Cargo.toml" {
+            builder.append_path_with_name(entry.path(), path_in_archive.join("Cargo.toml.orig"))
+                .with_context(|| format!("Failed to add {} to crate archive", entry.path().display()))?;
+        } else {
+            builder.append_path_with_name(entry.path(), entry_path_in_archive)
+                .with_context(|| format!("Failed to add {} to crate archive", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    // Create `<folder>/<name>-<version>.crate` with the given files placed under `<name>-<version>/`
+    pub fn write_crate_archive(folder: &Path, name: &str, version: &str, files: &[(&str, &str)]) -> PathBuf {
+        let crate_file_path = folder.join(format!("{}-{}.crate", name, version));
+        let crate_file = File::create(&crate_file_path).expect("Failed to create crate file");
+
+        let mut builder = tar::Builder::new(GzEncoder::new(crate_file, Compression::default()));
+
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+
+            builder.append_data(&mut header, format!("{}-{}/{}", name, version, path), content.as_bytes())
+                .expect("Failed to append file to crate archive");
+        }
+
+        builder.into_inner().expect("Failed to finish crate archive")
+            .finish().expect("Failed to finish crate archive compression");
+
+        crate_file_path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use super::test_utils::write_crate_archive;
+
+    #[test]
+    fn read_existing_file() {
+        let tmp_dir = tempdir::TempDir::new("crate_archive_test").expect("Failed to create temp dir");
+
+        let crate_file = write_crate_archive(tmp_dir.path(), "foo", "1.0.0", &[
+            ("Cargo.toml", "[package]\nname = \"foo\"\n"),
+            ("src/lib.rs", ""),
+        ]);
+
+        let content = read_file_from_crate_archive(&crate_file, "Cargo.toml").expect("Failed to read crate file");
+
+        assert_eq!(content, Some("[package]\nname = \"foo\"\n".to_string()));
+    }
+
+    #[test]
+    fn read_missing_file() {
+        let tmp_dir = tempdir::TempDir::new("crate_archive_test").expect("Failed to create temp dir");
+
+        let crate_file = write_crate_archive(tmp_dir.path(), "foo", "1.0.0", &[
+            ("Cargo.toml", "[package]\nname = \"foo\"\n"),
+        ]);
+
+        let content = read_file_from_crate_archive(&crate_file, "Cargo.lock").expect("Failed to read crate file");
+
+        assert_eq!(content, None);
+    }
+
+    #[test]
+    fn write_archive_from_folder() {
+        let tmp_dir = tempdir::TempDir::new("crate_archive_test").expect("Failed to create temp dir");
+        let package_folder = tmp_dir.path().join("package");
+
+        fs::create_dir_all(package_folder.join("src")).expect("Failed to create dir");
+        fs::create_dir_all(package_folder.join("target/debug")).expect("Failed to create dir");
+        fs::write(package_folder.join("Cargo.toml"), "[package]\nname = \"foo\"").expect("Failed to write file");
+        fs::write(package_folder.join("src/lib.rs"), "pub fn foo() {}").expect("Failed to write file");
+        fs::write(package_folder.join("target/debug/foo"), "binary").expect("Failed to write file");
+        fs::create_dir_all(package_folder.join("nested")).expect("Failed to create dir");
+        fs::write(package_folder.join("nested/Cargo.toml"), "[package]\nname = \"nested\"").expect("Failed to write file");
+
+        let crate_file_path = tmp_dir.path().join("foo-0.1.0.crate");
+        write_crate_archive_from_folder(package_folder.as_path(), "foo", "0.1.0", "[package]\nname = \"foo\"\nversion = \"0.1.0\"", crate_file_path.as_path())
+            .expect("Archive created");
+
+        assert_eq!(
+            read_file_from_crate_archive(crate_file_path.as_path(), "src/lib.rs").expect("Valid archive"),
+            Some("pub fn foo() {}".to_string())
+        );
+        assert_eq!(read_file_from_crate_archive(crate_file_path.as_path(), "target/debug/foo").expect("Valid archive"), None);
+        assert_eq!(read_file_from_crate_archive(crate_file_path.as_path(), "nested/Cargo.toml").expect("Valid archive"), None);
+        assert_eq!(
+            read_file_from_crate_archive(crate_file_path.as_path(), "Cargo.toml").expect("Valid archive"),
+            Some("[package]\nname = \"foo\"\nversion = \"0.1.0\"".to_string())
+        );
+        assert_eq!(
+            read_file_from_crate_archive(crate_file_path.as_path(), "Cargo.toml.orig").expect("Valid archive"),
+            Some("[package]\nname = \"foo\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::cli::CrateSpec;
+
+// Read the crates from list file, each line is `name[@version_req][ features=a,b]`
+pub fn read_crates_list(list_file_path: &Path) -> Result<Vec<CrateSpec>> {
+    let content = fs::read_to_string(list_file_path)
+        .with_context(|| format!("Failed to read crates list file at {}", list_file_path.display()))?;
+
+    parse_crates_list(content.as_str())
+        .with_context(|| format!("Failed to parse crates list file at {}", list_file_path.display()))
+}
+
+pub fn parse_crates_list(content: &str) -> Result<Vec<CrateSpec>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_number, line)| parse_crates_list_line(line).with_context(|| format!("Invalid line {}: {}", line_number, line)))
+        .collect()
+}
+
+fn parse_crates_list_line(line: &str) -> Result<CrateSpec> {
+    let mut parts = line.split_whitespace();
+
+    let mut spec: CrateSpec = parts
+        .next()
+        .expect("Line is not empty")
+        .parse()
+        .map_err(|e: String| anyhow!(e))?;
+
+    for part in parts {
+        let features = part
+            .strip_prefix("features=")
+            .ok_or_else(|| anyhow!("Unknown option {}, expected features=a,b", part))?;
+
+        spec.features
+            .get_or_insert_with(Vec::new)
+            .extend(features.split(',').filter(|feature| !feature.is_empty()).map(|feature| feature.to_string()));
+    }
+
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_list_with_comments() {
+        let content = r#"
+# Serialization
+serde@^1 features=derive,rc
+serde_json   # the latest version
+
+tokio@1.35 features=full
+        "#;
+
+        assert_eq!(parse_crates_list(content).expect("Valid list"), vec![
+            CrateSpec {
+                name: "serde".to_string(),
+                version_req: Some("^1".to_string()),
+                features: Some(vec!["derive".to_string(), "rc".to_string()]),
+            },
+            CrateSpec {
+                name: "serde_json".to_string(),
+                version_req: None,
+                features: None,
+            },
+            CrateSpec {
+                name: "tokio".to_string(),
+                version_req: Some("1.35".to_string()),
+                features: Some(vec!["full".to_string()]),
+            },
+        ]);
+    }
+
+    #[test]
+    fn fail_for_invalid_line() {
+        assert_eq!(parse_crates_list("serde@not-version").is_err(), true);
+        assert_eq!(parse_crates_list("serde derive").is_err(), true);
+    }
+}
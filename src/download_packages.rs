@@ -1,18 +1,36 @@
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
 use anyhow::{anyhow,  Result};
 use futures::StreamExt;
-use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use indicatif::ProgressBar;
 use reqwest::Client;
-use reqwest::header::{HeaderValue, USER_AGENT};
+use reqwest::header::{HeaderValue, AUTHORIZATION, USER_AGENT};
 use sha2::{Digest, Sha256};
 use tokio::fs::{create_dir_all};
 use std::fs::OpenOptions;
 use std::io::Write;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 use crate::collect_packages::{Package};
+use crate::registry::user_agent;
 use crate::spinners::progress_bar;
 
+// Downloaded crate that does not match its checksum from the index or Cargo.lock
+#[derive(Debug)]
+struct ChecksumMismatch {
+    url: String,
+    expected: String,
+    actual: String,
+}
+
+impl Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mismatched Hash for {}: expected: {} actual: {}", self.url, self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
 fn append_to_path(path: &Path, suffix: &str) -> PathBuf {
     let mut path = path.to_path_buf();
     path.set_extension(suffix);
@@ -31,6 +49,7 @@ async fn download_crate(
     url: &str,
     path: &Path,
     hash: &[u8],
+    auth_token: Option<&str>,
     user_agent: &HeaderValue,
     pb: &ProgressBar,
 ) -> Result<()> {
@@ -38,11 +57,15 @@ async fn download_crate(
         "Downloading {}",
         path.file_name().unwrap().to_str().unwrap()
     ));
-    let mut http_res = client
+    let mut request = client
         .get(url)
-        .header(USER_AGENT, user_agent)
-        .send()
-        .await?;
+        .header(USER_AGENT, user_agent);
+
+    if let Some(auth_token) = auth_token {
+        request = request.header(AUTHORIZATION, auth_token);
+    }
+
+    let mut http_res = request.send().await?;
     create_dir_all(path.parent().unwrap()).await?;
     let part_path = append_to_path(path, ".part");
 
@@ -51,6 +74,7 @@ async fn download_crate(
         let mut f = OpenOptions::new()
             .create(true)
             .write(true)
+            .truncate(true)
             .open(&part_path)?;
         let status = http_res.status();
         if status == 403 || status == 404 {
@@ -81,22 +105,22 @@ async fn download_crate(
         Ok(())
     } else {
         let badsha_path = append_to_path(path, ".badsha256");
-        std::fs::write(badsha_path, &f_hash)?;
-        Err(anyhow!(
-            "Mismatched Hash: expected: {:x?} actual: {:x}",
-            hash,
-            f_hash
-        ))
+        std::fs::write(badsha_path, f_hash)?;
+        Err(ChecksumMismatch {
+            url: url.to_string(),
+            expected: hex::encode(hash),
+            actual: hex::encode(f_hash),
+        }.into())
     }
 }
 
 pub async fn download_packages(packages: HashSet<Package>) -> Result<()> {
     info!("Downloading {} crates", packages.len());
     let client = Client::new();
-    let user_agent = HeaderValue::from_str(&format!("CargoCollect/{}", env!("CARGO_PKG_VERSION")))?;
+    let user_agent = user_agent();
     let pb = progress_bar(packages.len());
 
-    let tasks = futures::stream::iter(packages.into_iter())
+    let tasks = futures::stream::iter(packages)
         .map(|pkg| {
             let pb = pb.clone();
             let client = client.clone();
@@ -107,6 +131,7 @@ pub async fn download_packages(packages: HashSet<Package>) -> Result<()> {
                     &pkg.url,
                     &pkg.path,
                     &pkg.checksum,
+                    pkg.auth_token.as_deref(),
                     &user_agent,
                     &pb,
                 )
@@ -119,14 +144,26 @@ pub async fn download_packages(packages: HashSet<Package>) -> Result<()> {
         .collect::<Vec<_>>()
         .await;
 
+    let mut mismatched = 0;
+
     for t in tasks {
         match t.unwrap() {
             Ok(_) => {}
+            // Crate that does not match its checksum may have been tampered with, so it fails the collection
+            Err(err) if err.downcast_ref::<ChecksumMismatch>().is_some() => {
+                error!("{}", err);
+                mismatched += 1;
+            }
             Err(err) => {
                 warn!("Can't download crate: {}", err)
             }
         }
     }
+
+    if mismatched > 0 {
+        return Err(anyhow!("{} downloaded crates did not match their checksum", mismatched));
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::crate_archive::write_crate_archive_from_folder;
+use crate::package_manifest::normalize_manifest;
+use crate::parse_cargo_files::cargo_toml_file::{get_package_name_and_version, parse_cargo_file_from_path};
+use crate::parse_cargo_files::package_source::{GitReference, GitSource};
+use crate::target_filter::TargetFilter;
+use crate::{CrateToDownload, CratesToDownload};
+
+// Crate that is taken from git repository instead of registry
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GitDependency {
+    pub(crate) name: String,
+    pub(crate) source: GitSource,
+    // Version from Cargo.lock, the dependencies of locked crates are already in the Cargo.lock
+    pub(crate) locked_version: Option<String>,
+}
+
+// Clones of the git repositories, each repository is fetched once per run
+pub struct GitCache {
+    folder: PathBuf,
+    fetched: HashSet<String>,
+}
+
+impl GitCache {
+    pub fn new(folder: PathBuf) -> Self {
+        Self {
+            folder,
+            fetched: HashSet::new(),
+        }
+    }
+
+    // `$CARGO_HOME/cargo-collect/git`
+    pub fn default_folder() -> Result<PathBuf> {
+        Ok(home::cargo_home()?.join("cargo-collect").join("git"))
+    }
+
+    // Checkout the source in its repository clone and return the clone folder
+    fn checkout(&mut self, source: &GitSource) -> Result<PathBuf> {
+        let repository_folder = self.folder.join(repository_folder_name(source.url.as_str()));
+
+        if !repository_folder.join(".git").exists() {
+            fs::create_dir_all(&self.folder)
+                .with_context(|| format!("Failed to create git cache folder at {}", self.folder.display()))?;
+
+            info!("Cloning {}...", source.url);
+            run_git(None, &["clone", "--quiet", source.url.as_str(), path_to_str(repository_folder.as_path())?])?;
+            self.fetched.insert(source.url.clone());
+        } else if self.fetched.insert(source.url.clone()) {
+            info!("Fetching {}...", source.url);
+            run_git(Some(&repository_folder), &["fetch", "--quiet", "--force", "--tags", "origin", "+refs/heads/*:refs/remotes/origin/*"])?;
+        }
+
+        let revision = match (&source.precise, &source.reference) {
+            (Some(precise), _) => precise.clone(),
+            (None, Some(GitReference::Branch(branch))) => format!("origin/{}", branch),
+            (None, Some(GitReference::Tag(tag))) => format!("refs/tags/{}", tag),
+            (None, Some(GitReference::Rev(rev))) => rev.clone(),
+            (None, None) => "origin/HEAD".to_string(),
+        };
+
+        run_git(Some(&repository_folder), &["checkout", "--quiet", "--force", "--detach", revision.as_str()])?;
+        // Remove files that are left from previous checkouts
+        run_git(Some(&repository_folder), &["clean", "--quiet", "-d", "-x", "--force"])?;
+
+        Ok(repository_folder)
+    }
+}
+
+// Package the git dependencies into `.crate` files in the `git` folder of the output, apart from the registry crates of the same name and version,
+// and return the registry dependencies they need, git dependencies of git dependencies are packaged as well
+pub fn collect_git_dependencies(
+    git_cache: &mut GitCache,
+    git_dependencies: &[GitDependency],
+    output: &Path,
+    targets: &TargetFilter,
+) -> Result<CratesToDownload> {
+    let mut worklist = git_dependencies.to_vec();
+    let mut visited = HashSet::new();
+    let mut packaged = HashSet::new();
+    let mut crates_to_download = vec![];
+
+    while let Some(git_dependency) = worklist.pop() {
+        if !visited.insert(git_dependency.clone()) {
+            continue;
+        }
+
+        let cargo_file_path = package_git_dependency(git_cache, &git_dependency, output, &mut packaged)?;
+
+        if git_dependency.locked_version.is_some() {
+            continue;
+        }
+
+        let deps = parse_cargo_file_from_path(path_to_str(cargo_file_path.as_path())?.to_string(), targets)?;
+
+        crates_to_download.extend(deps.registry.into_keys().map(|key| CrateToDownload::new(key.name, key.version)));
+        worklist.extend(deps.git);
+    }
+
+    Ok(crates_to_download)
+}
+
+// Package the crate and its path dependencies from its repository and return the path of its Cargo.toml in the checkout
+fn package_git_dependency(
+    git_cache: &mut GitCache,
+    git_dependency: &GitDependency,
+    output: &Path,
+    packaged: &mut HashSet<(GitSource, PathBuf)>,
+) -> Result<PathBuf> {
+    let name = git_dependency.name.as_str();
+    let repository_folder = git_cache.checkout(&git_dependency.source)?;
+
+    let (cargo_file_path, version) = find_package_in_repository(repository_folder.as_path(), name)?
+        .ok_or_else(|| anyhow!("Crate {} was not found in git repository {}", name, git_dependency.source))?;
+
+    if let Some(locked_version) = &git_dependency.locked_version {
+        if *locked_version != version {
+            warn!("Crate {} from {} has version {} but Cargo.lock has {}", name, git_dependency.source, version, locked_version);
+        }
+    }
+
+    // Path dependencies of the crate are published with it, the same way `cargo publish` requires
+    let mut worklist = vec![cargo_file_path.clone()];
+
+    while let Some(package_cargo_file_path) = worklist.pop() {
+        let package_cargo_file_path = fs::canonicalize(&package_cargo_file_path)
+            .with_context(|| format!("Failed to find {} in git repository {}", package_cargo_file_path.display(), git_dependency.source))?;
+
+        // The same checkout folder has other revisions for other sources
+        if !packaged.insert((git_dependency.source.clone(), package_cargo_file_path.clone())) {
+            continue;
+        }
+
+        let manifest = normalize_manifest(package_cargo_file_path.as_path())
+            .with_context(|| format!("Failed to package {} from {}", package_cargo_file_path.display(), git_dependency.source))?;
+        let package_folder = package_cargo_file_path.parent().expect("cargo file path must be inside a directory");
+        let crate_file_path = git_packages_folder(output, git_dependency.source.url.as_str())?.join(format!("{}-{}.crate", manifest.name, manifest.version));
+
+        write_crate_archive_from_folder(package_folder, manifest.name.as_str(), manifest.version.as_str(), manifest.content.as_str(), crate_file_path.as_path())?;
+        info!("Packaged {} {} from {}", manifest.name, manifest.version, git_dependency.source);
+
+        worklist.extend(manifest.path_dependencies);
+    }
+
+    Ok(cargo_file_path)
+}
+
+// `<output>/git/<repository>`, so the registry crates in the output are never taken for the packaged crates of the repository
+fn git_packages_folder(output: &Path, url: &str) -> Result<PathBuf> {
+    let folder = output.join("git").join(repository_folder_name(url));
+
+    fs::create_dir_all(&folder)
+        .with_context(|| format!("Failed to create folder for git packages at {}", folder.display()))?;
+
+    Ok(folder)
+}
+
+// The Cargo.toml of the package in the repository with its version
+fn find_package_in_repository(repository_folder: &Path, name: &str) -> Result<Option<(PathBuf, String)>> {
+    let pattern = repository_folder.join("**").join("Cargo.toml");
+    let pattern = path_to_str(pattern.as_path())?;
+
+    for cargo_file_path in glob::glob(pattern)?.filter_map(|path| path.ok()) {
+        let in_ignored_folder = cargo_file_path
+            .strip_prefix(repository_folder)?
+            .components()
+            .any(|component| component.as_os_str() == ".git" || component.as_os_str() == "target");
+
+        if in_ignored_folder {
+            continue;
+        }
+
+        if let Some((package_name, version)) = get_package_name_and_version(cargo_file_path.as_path()) {
+            if package_name == name {
+                return Ok(Some((cargo_file_path, version)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// Readable and unique folder name for the repository, e.g. `serde-1a2b3c4d5e6f7a8b`
+fn repository_folder_name(url: &str) -> String {
+    let repository_name = url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git");
+
+    let hash = Sha256::digest(url.as_bytes());
+
+    format!("{}-{}", repository_name, hex::encode(&hash[..8]))
+}
+
+fn path_to_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| anyhow!("Path {} is not valid UTF-8", path.display()))
+}
+
+fn run_git(folder: Option<&Path>, args: &[&str]) -> Result<()> {
+    let mut command = Command::new("git");
+
+    if let Some(folder) = folder {
+        command.arg("-C").arg(folder);
+    }
+
+    let output = command
+        .args(args)
+        // Fail instead of waiting for credentials
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::crate_archive::read_file_from_crate_archive;
+
+    fn git(folder: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(folder)
+            .args(["-c", "user.name=test", "-c", "user.email=test@test", "-c", "init.defaultBranch=main"])
+            .args(args)
+            .output()
+            .expect("Failed to run git")
+            .status;
+
+        assert_eq!(status.success(), true, "git {:?} failed", args);
+    }
+
+    // Repository with workspace that has `my-git-crate` in `crates/my-git-crate` with path dependency on `my-git-helper`,
+    // returns its `file://` url
+    fn create_repository(folder: &Path) -> String {
+        let crate_folder = folder.join("crates/my-git-crate");
+        fs::create_dir_all(crate_folder.join("src")).expect("Failed to create dir");
+
+        // language=toml
+        fs::write(folder.join("Cargo.toml"), r#"
+[workspace]
+members = ["crates/*"]
+
+[workspace.package]
+version = "0.1.0"
+        "#).expect("Failed to write file");
+
+        // language=toml
+        fs::write(crate_folder.join("Cargo.toml"), r#"
+[package]
+name = "my-git-crate"
+version.workspace = true
+
+[dependencies]
+serde = "1.0"
+my-git-helper = { path = "../my-git-helper" }
+        "#).expect("Failed to write file");
+
+        fs::write(crate_folder.join("src/lib.rs"), "pub fn version() -> u32 { 1 }").expect("Failed to write file");
+
+        let helper_folder = folder.join("crates/my-git-helper");
+        fs::create_dir_all(helper_folder.join("src")).expect("Failed to create dir");
+        fs::write(helper_folder.join("Cargo.toml"), "[package]\nname = \"my-git-helper\"\nversion.workspace = true\n").expect("Failed to write file");
+        fs::write(helper_folder.join("src/lib.rs"), "").expect("Failed to write file");
+
+        git(folder, &["init", "--quiet"]);
+        git(folder, &["add", "-A"]);
+        git(folder, &["commit", "--quiet", "-m", "first"]);
+        git(folder, &["tag", "v1"]);
+
+        format!("file://{}", folder.display())
+    }
+
+    fn git_dependency(url: &str, reference: Option<GitReference>) -> GitDependency {
+        GitDependency {
+            name: "my-git-crate".to_string(),
+            source: GitSource {
+                url: url.to_string(),
+                reference,
+                precise: None,
+            },
+            locked_version: None,
+        }
+    }
+
+    #[test]
+    fn package_git_dependency_and_collect_its_deps() {
+        let tmp_dir = tempdir::TempDir::new("git_dependencies_test").expect("Failed to create temp dir");
+        let repository = tmp_dir.path().join("repository");
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir_all(&output).expect("Failed to create dir");
+
+        let url = create_repository(repository.as_path());
+        let mut git_cache = GitCache::new(tmp_dir.path().join("cache"));
+
+        let crates_to_download = collect_git_dependencies(
+            &mut git_cache,
+            &[git_dependency(url.as_str(), None)],
+            output.as_path(),
+            &TargetFilter::default(),
+        ).expect("Collect successfully");
+
+        let packages_folder = output.join("git").join(repository_folder_name(url.as_str()));
+
+        assert_eq!(crates_to_download, vec![CrateToDownload::new("serde", "1.0")]);
+        // The registry crate of the same name and version in the output is a different crate
+        assert_eq!(output.join("my-git-crate-0.1.0.crate").exists(), false);
+        assert_eq!(
+            read_file_from_crate_archive(packages_folder.join("my-git-crate-0.1.0.crate").as_path(), "src/lib.rs").expect("Valid archive"),
+            Some("pub fn version() -> u32 { 1 }".to_string())
+        );
+
+        // The packaged Cargo.toml does not need the workspace and the path dependency is packaged as well
+        let cargo_file_content = read_file_from_crate_archive(packages_folder.join("my-git-crate-0.1.0.crate").as_path(), "Cargo.toml")
+            .expect("Valid archive")
+            .expect("Must have Cargo.toml");
+        let cargo: toml::Table = cargo_file_content.parse().expect("Valid Cargo.toml");
+
+        assert_eq!(cargo["package"]["version"].as_str(), Some("0.1.0"));
+        assert_eq!(cargo["dependencies"]["my-git-helper"].as_table().map(|detail| detail.keys().map(String::as_str).collect::<Vec<_>>()), Some(vec!["version"]));
+        assert_eq!(packages_folder.join("my-git-helper-0.1.0.crate").is_file(), true);
+    }
+
+    #[test]
+    fn checkout_by_reference() {
+        let tmp_dir = tempdir::TempDir::new("git_dependencies_test").expect("Failed to create temp dir");
+        let repository = tmp_dir.path().join("repository");
+        let output = tmp_dir.path().join("deps");
+        fs::create_dir_all(&output).expect("Failed to create dir");
+
+        let url = create_repository(repository.as_path());
+
+        // New version on the branch after the tag
+        fs::write(repository.join("crates/my-git-crate/src/lib.rs"), "pub fn version() -> u32 { 2 }").expect("Failed to write file");
+        fs::write(repository.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n[workspace.package]\nversion = \"0.2.0\"").expect("Failed to write file");
+        git(repository.as_path(), &["commit", "--quiet", "-a", "-m", "second"]);
+
+        let mut git_cache = GitCache::new(tmp_dir.path().join("cache"));
+
+        collect_git_dependencies(
+            &mut git_cache,
+            &[
+                git_dependency(url.as_str(), Some(GitReference::Tag("v1".to_string()))),
+                git_dependency(url.as_str(), Some(GitReference::Branch("main".to_string()))),
+            ],
+            output.as_path(),
+            &TargetFilter::default(),
+        ).expect("Collect successfully");
+
+        let packages_folder = output.join("git").join(repository_folder_name(url.as_str()));
+
+        assert_eq!(
+            read_file_from_crate_archive(packages_folder.join("my-git-crate-0.1.0.crate").as_path(), "src/lib.rs").expect("Valid archive"),
+            Some("pub fn version() -> u32 { 1 }".to_string())
+        );
+        assert_eq!(
+            read_file_from_crate_archive(packages_folder.join("my-git-crate-0.2.0.crate").as_path(), "src/lib.rs").expect("Valid archive"),
+            Some("pub fn version() -> u32 { 2 }".to_string())
+        );
+    }
+
+    #[test]
+    fn fail_for_missing_crate_in_repository() {
+        let tmp_dir = tempdir::TempDir::new("git_dependencies_test").expect("Failed to create temp dir");
+        let url = create_repository(tmp_dir.path().join("repository").as_path());
+        let mut git_cache = GitCache::new(tmp_dir.path().join("cache"));
+
+        let mut dependency = git_dependency(url.as_str(), None);
+        dependency.name = "not-exists".to_string();
+
+        assert_eq!(collect_git_dependencies(&mut git_cache, &[dependency], tmp_dir.path(), &TargetFilter::default()).is_err(), true);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::parse_cargo_files::package_source::PackageSource;
+
+// The `.crates2.json` file that cargo keeps the installed packages in
+#[derive(Debug, Deserialize)]
+struct CratesInstalls {
+    // The key is package id, `name version (source)`
+    installs: HashMap<String, InstallInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallInfo {
+    #[serde(default)]
+    features: Vec<String>,
+
+    #[serde(default)]
+    all_features: bool,
+
+    #[serde(default)]
+    no_default_features: bool,
+}
+
+// Package that was installed with `cargo install`
+#[derive(Debug, PartialEq)]
+pub struct InstalledPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) source: PackageSource,
+    pub(crate) features: Vec<String>,
+    // Installed with `--all-features`
+    pub(crate) all_features: bool,
+    // Installed with `--no-default-features`
+    pub(crate) no_default_features: bool,
+}
+
+// Path of `.crates2.json` in cargo home, `CARGO_HOME` is respected
+pub fn default_installed_file() -> Result<PathBuf> {
+    let cargo_home = home::cargo_home().context("Failed to find cargo home")?;
+
+    Ok(cargo_home.join(".crates2.json"))
+}
+
+pub fn read_installed_packages(installed_file_path: &Path) -> Result<Vec<InstalledPackage>> {
+    let content = fs::read_to_string(installed_file_path)
+        .with_context(|| format!("Failed to read installed packages from {}", installed_file_path.display()))?;
+
+    parse_installed_packages(content.as_str())
+        .with_context(|| format!("Failed to parse installed packages from {}", installed_file_path.display()))
+}
+
+fn parse_installed_packages(content: &str) -> Result<Vec<InstalledPackage>> {
+    let installs: CratesInstalls = serde_json::from_str(content)?;
+
+    let mut packages = installs.installs
+        .into_iter()
+        .map(|(package_id, info)| {
+            let mut package = parse_package_id(package_id.as_str())?;
+            package.features = info.features;
+            package.all_features = info.all_features;
+            package.no_default_features = info.no_default_features;
+
+            Ok(package)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(packages)
+}
+
+fn parse_package_id(package_id: &str) -> Result<InstalledPackage> {
+    let invalid = || anyhow!("Invalid package id {}, expected `name version (source)`", package_id);
+
+    let (name, rest) = package_id.split_once(' ').ok_or_else(invalid)?;
+    let (version, source) = rest.split_once(' ').ok_or_else(invalid)?;
+    let source = source
+        .strip_prefix('(')
+        .and_then(|source| source.strip_suffix(')'))
+        .ok_or_else(invalid)?;
+
+    Ok(InstalledPackage {
+        name: name.to_string(),
+        version: version.to_string(),
+        source: source.parse()?,
+        features: vec![],
+        all_features: false,
+        no_default_features: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::parse_cargo_files::package_source::{GitSource, CRATES_IO_INDEX_URL};
+
+    use super::*;
+
+    #[test]
+    fn parse_installed_packages_of_all_sources() {
+        // language=json
+        let content = r#"{
+            "installs": {
+                "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                    "version_req": null,
+                    "bins": ["rg"],
+                    "features": ["pcre2"],
+                    "all_features": false,
+                    "no_default_features": false,
+                    "profile": "release",
+                    "target": "x86_64-unknown-linux-gnu",
+                    "rustc": "rustc 1.76.0"
+                },
+                "my-tool 0.2.0 (git+https://github.com/foo/my-tool#3b4c5d6e)": {
+                    "bins": ["my-tool"],
+                    "all_features": true
+                },
+                "local-tool 0.1.0 (path+file:///home/user/local-tool)": {
+                    "bins": ["local-tool"],
+                    "no_default_features": true
+                }
+            }
+        }"#;
+
+        assert_eq!(parse_installed_packages(content).unwrap(), vec![
+            InstalledPackage {
+                name: "local-tool".to_string(),
+                version: "0.1.0".to_string(),
+                source: PackageSource::Path("file:///home/user/local-tool".to_string()),
+                features: vec![],
+                all_features: false,
+                no_default_features: true,
+            },
+            InstalledPackage {
+                name: "my-tool".to_string(),
+                version: "0.2.0".to_string(),
+                source: PackageSource::Git(GitSource {
+                    url: "https://github.com/foo/my-tool".to_string(),
+                    reference: None,
+                    precise: Some("3b4c5d6e".to_string()),
+                }),
+                features: vec![],
+                all_features: true,
+                no_default_features: false,
+            },
+            InstalledPackage {
+                name: "ripgrep".to_string(),
+                version: "14.1.0".to_string(),
+                source: PackageSource::Registry(CRATES_IO_INDEX_URL.to_string()),
+                features: vec!["pcre2".to_string()],
+                all_features: false,
+                no_default_features: false,
+            },
+        ]);
+    }
+
+    #[test]
+    fn fail_for_invalid_package_id() {
+        assert_eq!(parse_package_id("ripgrep 14.1.0").is_err(), true);
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use semver::{Version as SemVersion, VersionReq};
+use tracing::{info, warn};
+
+use crate::crate_archive::read_file_from_crate_archive;
+use crate::{CrateToDownload, CratesToDownload};
+use crate::parse_cargo_files::cargo_toml_file::{get_deps_maps_from_cargo, merge_target_dependencies, CargoToml, PackageVersion};
+use crate::target_filter::TargetFilter;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LocalCrateVersion {
+    pub(crate) name: String,
+    pub(crate) version: SemVersion,
+    pub(crate) dependencies: CratesToDownload,
+}
+
+// In-memory index built from the `.crate` files of a previous collection
+#[derive(Debug, Default)]
+pub struct LocalRegistry {
+    crates: HashMap<String, Vec<LocalCrateVersion>>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LocalResolution {
+    // Crate versions from the local registry that are part of the dependency closure
+    pub(crate) resolved: Vec<LocalCrateVersion>,
+
+    // Crate name and version requirement that no local crate file satisfies
+    pub(crate) missing: CratesToDownload,
+}
+
+impl LocalRegistry {
+    pub fn from_folder(folder: &Path) -> Result<Self> {
+        let mut registry = LocalRegistry::default();
+
+        let entries = std::fs::read_dir(folder)
+            .with_context(|| format!("Failed to read folder {}", folder.display()))?;
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+
+            if path.extension().is_none_or(|extension| extension != "crate") {
+                continue;
+            }
+
+            match read_local_crate_version(&path) {
+                Ok(local_crate) => registry.add(local_crate),
+                Err(err) => warn!("Skipped, Can't read crate file {}: {:#}", path.display(), err),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn add(&mut self, local_crate: LocalCrateVersion) {
+        self.crates.entry(local_crate.name.clone()).or_default().push(local_crate);
+    }
+
+    pub fn crates_count(&self) -> usize {
+        self.crates.values().map(|versions| versions.len()).sum()
+    }
+
+    // Highest local version of the crate that match the version requirement
+    fn find_highest_matching_version(&self, crate_name: &str, version_req: &VersionReq) -> Option<&LocalCrateVersion> {
+        self.crates
+            .get(crate_name)?
+            .iter()
+            .filter(|local_crate| version_req.matches(&local_crate.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+
+    // Resolve the dependencies recursively using only the crates in the local registry
+    pub fn resolve(&self, worklist: &mut CratesToDownload) -> Result<LocalResolution> {
+        let mut selected: HashMap<String, HashSet<SemVersion>> = HashMap::new();
+        let mut resolved = vec![];
+        let mut missing = BTreeSet::new();
+
+        while let Some(crate_to_download) = worklist.pop() {
+            // The dependencies of the replacement are resolved instead
+            if crate_to_download.is_patched() {
+                continue;
+            }
+
+            let version_req = VersionReq::parse(crate_to_download.version_req.as_str())
+                .map_err(|err| anyhow!("Invalid version requirement {} for crate {}: {}", crate_to_download.version_req, crate_to_download.name, err))?;
+
+            // Reuse a version that was already selected for this crate when it match
+            let already_selected = selected
+                .get(&crate_to_download.name)
+                .is_some_and(|versions| versions.iter().any(|version| version_req.matches(version)));
+
+            if already_selected {
+                continue;
+            }
+
+            let Some(local_crate) = self.find_highest_matching_version(&crate_to_download.name, &version_req) else {
+                missing.insert(crate_to_download);
+                continue;
+            };
+
+            selected.entry(crate_to_download.name.clone()).or_default().insert(local_crate.version.clone());
+            worklist.extend(local_crate.dependencies.iter().map(|dependency| dependency.clone().with_patches(crate_to_download.patches.clone())));
+            resolved.push(local_crate.clone());
+        }
+
+        Ok(LocalResolution {
+            resolved: resolved
+                .into_iter()
+                .sorted_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+                .collect(),
+            missing: missing.into_iter().collect(),
+        })
+    }
+}
+
+// The name and version are taken from the Cargo.toml of the crate, the file name is ambiguous for names and versions with `-`
+fn read_local_crate_version(crate_file_path: &Path) -> Result<LocalCrateVersion> {
+    let cargo_file_content = read_file_from_crate_archive(crate_file_path, "Cargo.toml")?
+        .ok_or_else(|| anyhow!("Crate file does not contain Cargo.toml"))?;
+
+    let mut cargo: CargoToml = toml::from_str(&cargo_file_content).context("Failed to parse Cargo.toml")?;
+
+    let package = cargo.package.clone().ok_or_else(|| anyhow!("Cargo.toml does not have [package]"))?;
+    let Some(PackageVersion::Version(version)) = package.version else {
+        return Err(anyhow!("Cargo.toml of {} does not have version", package.name));
+    };
+
+    // The dependencies of all platforms may be needed
+    merge_target_dependencies(&mut cargo, &TargetFilter::default());
+
+    let (deps, _) = get_deps_maps_from_cargo(cargo);
+
+    Ok(LocalCrateVersion {
+        name: package.name,
+        version: SemVersion::parse(version.as_str())?,
+        dependencies: deps
+            .into_keys()
+            .map(|key| CrateToDownload::new(key.name, key.version))
+            .collect(),
+    })
+}
+
+pub fn resolve_from_local_folder(folder: &Path, worklist: &mut CratesToDownload) -> Result<LocalResolution> {
+    info!("Reading crates from {}...", folder.display());
+    let registry = LocalRegistry::from_folder(folder)?;
+    info!("Found {} crates in {}", registry.crates_count(), folder.display());
+
+    let resolution = registry.resolve(worklist)?;
+
+    info!(
+        "Resolved {} crates from {}, {} missing",
+        resolution.resolved.len(),
+        folder.display(),
+        resolution.missing.len()
+    );
+
+    Ok(resolution)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::crate_archive::test_utils::write_crate_archive;
+
+    fn create_normalized_cargo_file(name: &str, version: &str, add: &str) -> String {
+        // language=toml
+        let base = format!(r#"
+[package]
+edition = "2021"
+name = "{}"
+version = "{}"
+        "#, name, version);
+
+        format!("{}\n{}", base, add)
+    }
+
+    fn create_local_registry_folder(crates: &[(&str, &str, &str)]) -> tempdir::TempDir {
+        let tmp_dir = tempdir::TempDir::new("local_registry_test").expect("Failed to create temp dir");
+
+        crates.iter().for_each(|(name, version, deps)| {
+            write_crate_archive(tmp_dir.path(), name, version, &[
+                ("Cargo.toml", create_normalized_cargo_file(name, version, deps).as_str()),
+                ("src/lib.rs", ""),
+            ]);
+        });
+
+        tmp_dir
+    }
+
+    fn names_and_versions(resolution: &LocalResolution) -> Vec<(String, String)> {
+        resolution.resolved
+            .iter()
+            .map(|local_crate| (local_crate.name.clone(), local_crate.version.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn read_crates_from_folder() {
+        // language=toml
+        let folder = create_local_registry_folder(&[
+            ("foo", "1.0.0", r#"
+[dependencies.bar]
+version = "^0.2"
+
+[dev-dependencies.baz]
+version = "1"
+            "#),
+            ("bar", "0.2.1", ""),
+        ]);
+
+        let registry = LocalRegistry::from_folder(folder.path()).expect("Failed to read folder");
+
+        assert_eq!(registry.crates_count(), 2);
+
+        let foo = registry.find_highest_matching_version("foo", &VersionReq::STAR).expect("Must have foo");
+
+        assert_eq!(
+            foo.dependencies.iter().cloned().sorted().collect_vec(),
+            vec![
+                CrateToDownload::new("bar", "^0.2"),
+                CrateToDownload::new("baz", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_prerelease_crates() {
+        let folder = create_local_registry_folder(&[
+            ("foo", "1.0.0-beta.1", ""),
+            ("foo-bar-2d", "0.3.0-rc-1", ""),
+        ]);
+
+        let registry = LocalRegistry::from_folder(folder.path()).expect("Failed to read folder");
+
+        assert_eq!(registry.crates_count(), 2);
+        assert_eq!(
+            registry.find_highest_matching_version("foo", &VersionReq::parse("=1.0.0-beta.1").unwrap()).map(|foo| foo.version.to_string()),
+            Some("1.0.0-beta.1".to_string())
+        );
+        assert_eq!(
+            registry.find_highest_matching_version("foo-bar-2d", &VersionReq::parse("=0.3.0-rc-1").unwrap()).map(|foo| foo.version.to_string()),
+            Some("0.3.0-rc-1".to_string())
+        );
+    }
+
+    #[test]
+    fn skip_crate_with_malformed_cargo_file() {
+        let folder = create_local_registry_folder(&[("bar", "0.2.1", "")]);
+        write_crate_archive(folder.path(), "broken", "1.0.0", &[("Cargo.toml", "[package")]);
+
+        let registry = LocalRegistry::from_folder(folder.path()).expect("Failed to read folder");
+
+        assert_eq!(registry.crates_count(), 1);
+        assert_eq!(registry.find_highest_matching_version("broken", &VersionReq::STAR), None);
+    }
+
+    #[test]
+    fn resolve_full_closure() {
+        // language=toml
+        let folder = create_local_registry_folder(&[
+            ("foo", "1.0.0", r#"
+[dependencies.bar]
+version = "^0.2"
+            "#),
+            ("bar", "0.2.1", r#"
+[dependencies.baz]
+version = "1"
+            "#),
+            ("bar", "0.2.3", r#"
+[dependencies.baz]
+version = "1"
+            "#),
+            ("baz", "1.4.0", ""),
+            ("unrelated", "1.0.0", ""),
+        ]);
+
+        let resolution = resolve_from_local_folder(
+            folder.path(),
+            &mut vec![CrateToDownload::new("foo", "*")],
+        ).expect("Failed to resolve");
+
+        assert_eq!(names_and_versions(&resolution), vec![
+            ("bar".to_string(), "0.2.3".to_string()),
+            ("baz".to_string(), "1.4.0".to_string()),
+            ("foo".to_string(), "1.0.0".to_string()),
+        ]);
+        assert_eq!(resolution.missing, vec![]);
+    }
+
+    #[test]
+    fn report_missing_crates() {
+        // language=toml
+        let folder = create_local_registry_folder(&[
+            ("foo", "1.0.0", r#"
+[dependencies.bar]
+version = "^0.2"
+
+[dependencies.old]
+version = "2"
+            "#),
+            ("old", "1.0.0", ""),
+        ]);
+
+        let resolution = resolve_from_local_folder(
+            folder.path(),
+            &mut vec![
+                CrateToDownload::new("foo", "^1"),
+                CrateToDownload::new("not-mirrored", "*"),
+            ],
+        ).expect("Failed to resolve");
+
+        assert_eq!(names_and_versions(&resolution), vec![
+            ("foo".to_string(), "1.0.0".to_string()),
+        ]);
+        assert_eq!(resolution.missing, vec![
+            CrateToDownload::new("bar", "^0.2"),
+            CrateToDownload::new("not-mirrored", "*"),
+            CrateToDownload::new("old", "2"),
+        ]);
+    }
+
+    #[test]
+    fn resolve_renamed_dependency() {
+        // language=toml
+        let folder = create_local_registry_folder(&[
+            ("foo", "1.0.0", r#"
+[dependencies.renamed]
+version = "1"
+package = "bar"
+            "#),
+            ("bar", "1.0.0", ""),
+        ]);
+
+        let resolution = resolve_from_local_folder(
+            folder.path(),
+            &mut vec![CrateToDownload::new("foo", "*")],
+        ).expect("Failed to resolve");
+
+        assert_eq!(names_and_versions(&resolution), vec![
+            ("bar".to_string(), "1.0.0".to_string()),
+            ("foo".to_string(), "1.0.0".to_string()),
+        ]);
+    }
+}
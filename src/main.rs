@@ -1,60 +1,184 @@
-#[macro_use]
-extern crate derive_builder;
-
+mod build_std;
 mod cli;
 mod collect_packages;
+mod crate_archive;
+mod crates_list;
 mod download_packages;
+mod git_dependencies;
+mod installed;
+mod local_registry;
+mod package_manifest;
+mod registry;
+mod scan;
 mod spinners;
 mod parse_cargo_files;
+mod python_sdist;
+mod sbom;
+mod target_filter;
 
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
-use anyhow::{anyhow, Result};
-use crates_index::GitIndex;
-
-use crate::cli::Cli;
-use crate::collect_packages::collect_packages;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use semver::{Version, VersionReq};
+use tracing::{info, warn};
+
+use crate::build_std::{find_std_lock_file, find_sysroot};
+use crate::cli::{Cli, Command, CrateSpec, InstallArgs, WhyArgs};
+use crate::crate_archive::read_file_from_crate_archive;
+use crate::crates_list::read_crates_list;
+use crate::collect_packages::{collect_locked_packages, collect_packages, find_package, LockedPackage};
 use crate::download_packages::download_packages;
-use crate::parse_cargo_files::cargo_toml_file::{parse_cargo_file_from_path};
-use crate::parse_cargo_files::parse_lock_file::parse_cargo_lock_file;
+use crate::git_dependencies::{collect_git_dependencies, GitCache, GitDependency};
+use crate::local_registry::resolve_from_local_folder;
+use crate::parse_cargo_files::cargo_metadata::read_cargo_metadata;
+use crate::parse_cargo_files::cargo_toml_file::{canonicalize_cargo_file_path, find_workspace_root, parse_cargo_file_from_path, read_cargo_file, parse_workspace_members_from_path, CargoFileDependencies};
+use crate::parse_cargo_files::lock_file_graph::LockFileGraph;
+use crate::parse_cargo_files::package_source::{is_crates_io_index_url, PackageSource};
+use crate::parse_cargo_files::parse_lock_file::{parse_cargo_lock_file, Package as LockFilePackage};
+use crate::installed::{default_installed_file, read_installed_packages};
+use crate::python_sdist::PythonSdist;
+use crate::sbom::read_sbom;
+use crate::registry::{configured_index_url, is_same_index_url, Registries, Registry, CRATES_IO_REGISTRY_NAME};
+use crate::scan::scan_folder;
+use crate::target_filter::TargetFilter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CrateToDownload {
+    pub(crate) name: String,
+    pub(crate) version_req: String,
+    // Index url of the registry to take the crate from, the default registry when missing
+    pub(crate) registry: Option<String>,
+    // Features to enable, when set only the optional dependencies of these and the default features are collected
+    pub(crate) features: Option<Vec<String>>,
+    // The default features are enabled with the requested features, like `--no-default-features` when false
+    pub(crate) default_features: bool,
+    // Checksum from Cargo.lock, the index checksum of the crate must be the same
+    pub(crate) checksum: Option<String>,
+    // Crates that `[patch]` or `[replace]` of the manifest replace, they are not collected in the dependencies of this crate
+    pub(crate) patches: Patches,
+}
+
+pub type Patches = Arc<BTreeSet<PatchedCrate>>;
+
+// Crate from `[patch]` or `[replace]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PatchedCrate {
+    // Index url of the registry, crates.io when missing
+    pub(crate) registry: Option<String>,
+    pub(crate) name: String,
+}
+
+impl CrateToDownload {
+    pub fn new(name: impl Into<String>, version_req: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version_req: version_req.into(),
+            registry: None,
+            features: None,
+            default_features: true,
+            checksum: None,
+            patches: Patches::default(),
+        }
+    }
+
+    pub fn with_registry(mut self, registry: Option<String>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn with_features(mut self, features: Option<Vec<String>>) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_default_features(mut self, default_features: bool) -> Self {
+        self.default_features = default_features;
+        self
+    }
+
+    pub fn with_checksum(mut self, checksum: Option<String>) -> Self {
+        self.checksum = checksum;
+        self
+    }
 
-pub type CratesToDownload = Vec<(
-    String, /* Crate name */
-    String /* Crate version requirement */
-)>;
+    pub fn with_patches(mut self, patches: Patches) -> Self {
+        self.patches = patches;
+        self
+    }
+
+    // The crate is replaced by the patches it is reached with, the default registry is crates.io for the manifests
+    pub fn is_patched(&self) -> bool {
+        self.patches.iter().any(|patched| patched.name == self.name && match (&patched.registry, &self.registry) {
+            (None, None) => true,
+            (None, Some(index_url)) | (Some(index_url), None) => is_crates_io_index_url(index_url),
+            (Some(patched_index_url), Some(index_url)) => is_same_index_url(patched_index_url, index_url),
+        })
+    }
+}
+
+pub type CratesToDownload = Vec<CrateToDownload>;
 
 async fn run(args: Cli) -> Result<()> {
-    let mut index = GitIndex::new_cargo_default()?;
+    if let Some(Command::Why(why)) = &args.command {
+        return run_why(why);
+    }
+
+    if let Some(resolve_from) = args.resolve_from.clone() {
+        return run_resolve_from_local_folder(args, &resolve_from);
+    }
+
+    if args.no_index {
+        return run_without_index(args).await;
+    }
+
+    let mut registry = match (&args.registry, &args.index_provider) {
+        (Some(registry_name), _) => Registry::from_name(registry_name).await?,
+        (None, Some(index_provider)) => Registry::from_index_provider(index_provider)?,
+        (None, None) => Registry::crates_io()?,
+    };
 
     if args.update_index {
         println!("Updating index...");
-        index.update()?;
+        registry.update()?;
         println!("Index updated.");
     }
 
-    let mut crates_to_download: CratesToDownload;
+    let mut registries = Registries::new(registry);
 
     let output_path = args.output.clone();
+    let targets = TargetFilter::from_targets(&args.target)?;
 
-    if args.crate_name.is_some() {
-        crates_to_download = get_crate_names_and_versions_from_cli_arg(&index, args)?;
-    } else if args.cargo_file.is_some() {
-        crates_to_download = get_crate_names_and_versions_from_cargo_file(args);
-    } else if args.cargo_lock_file.is_some() {
-        crates_to_download = get_crate_names_and_versions_from_cargo_lock_file(args);
-    } else {
-        unreachable!("Should not reach here");
+    // All the inputs are combined into one worklist
+    let mut crates_to_download = get_crate_names_and_versions_from_specs(registries.default_registry(), get_crate_specs(&args)?).await?;
+    let (crates_from_files, mut git_dependencies) = get_crate_names_and_versions_from_files(&args, &targets)?;
+    crates_to_download.extend(crates_from_files);
+
+    if let Some(Command::Install(install)) = &args.command {
+        let (crates, git) = get_crate_names_and_versions_from_install(&mut registries, install, &output_path).await?;
+        crates_to_download.extend(crates);
+        git_dependencies.extend(git);
     }
 
-    if !output_path.try_exists().expect("Failed to check directory creation") {
-        fs::create_dir(&output_path)
-            .expect(format!("Failed to create output directory at {:?}", output_path.as_path()).as_str());
+    if args.installed {
+        let (crates, git) = get_crate_names_and_versions_from_installed(&mut registries, &output_path).await?;
+        crates_to_download.extend(crates);
+        git_dependencies.extend(git);
     }
 
+    create_output_folder(&output_path);
+
+    // Package the git dependencies and add the registry crates they depend on.
+    crates_to_download.extend(package_git_dependencies(&git_dependencies, &output_path, &targets)?);
+
     // Collect the dependencies recursively.
     let packages = collect_packages(
-        &index,
+        &mut registries,
         &mut crates_to_download,
         &output_path,
+        &targets,
     )
         .await?;
 
@@ -64,68 +188,631 @@ async fn run(args: Cli) -> Result<()> {
     Ok(())
 }
 
-fn get_crate_names_and_versions_from_cli_arg(index: &GitIndex, args: Cli) -> Result<CratesToDownload> {
-    let crate_name = args.crate_name.expect("Must have crate name");
+// Print the chains of dependencies that bring the matching packages into Cargo.lock, only from the lock file
+fn run_why(why: &WhyArgs) -> Result<()> {
+    let cargo_lock_file_path = why.cargo_lock_file.as_path();
+    let content = fs::read_to_string(cargo_lock_file_path)
+        .with_context(|| format!("Failed to read Cargo.lock file at {}", cargo_lock_file_path.display()))?;
+    let cargo_lock = parse_cargo_lock_file(content)
+        .with_context(|| format!("Failed to parse Cargo.lock file at {}", cargo_lock_file_path.display()))?;
 
-    // Take the version requirement from args if exists,
-    // otherwise define the highest normal version as the version req.
-    let version_req = if let Some(version_req) = args.crate_version_req {
-        version_req
-    } else {
-        get_version_requirements_for_crate(index, crate_name.clone())?
+    let graph = LockFileGraph::new(cargo_lock.package.unwrap_or_default())?;
+
+    let version_req = why.krate.version_req
+        .as_deref()
+        .map(VersionReq::parse)
+        .transpose()?;
+
+    let targets = graph.packages()
+        .iter()
+        .enumerate()
+        .filter(|(_, package)| package.name == why.krate.name)
+        .filter(|(_, package)| version_req.as_ref().is_none_or(|version_req| {
+            Version::parse(package.version.as_str()).is_ok_and(|version| version_req.matches(&version))
+        }))
+        .map(|(index, _)| index)
+        .collect_vec();
+
+    if targets.is_empty() {
+        return Err(anyhow!("{} is not in {}", why.krate.name, cargo_lock_file_path.display()));
+    }
+
+    for target in targets {
+        let package = &graph.packages()[target];
+        println!("{} {}", package.name, package.version);
+
+        for path in graph.why(target) {
+            println!("    {}", path.iter().map(|package| format!("{} {}", package.name, package.version)).join(" -> "));
+        }
+    }
+
+    Ok(())
+}
+
+// Download the packages of Cargo.lock without cloning the index,
+// only the `config.json` of each registry is needed for the download url
+async fn run_without_index(args: Cli) -> Result<()> {
+    let registry = match &args.registry {
+        Some(registry_name) => Registry::from_name(registry_name).await?,
+        None => Registry::crates_io_sparse().await?,
     };
 
-    return Ok(vec![(crate_name.clone(), version_req)]);
+    let mut registries = Registries::new(registry);
+
+    let output_path = args.output.clone();
+
+    let mut locked_packages = vec![];
+    let mut git_dependencies = vec![];
+
+    for cargo_lock_file_path in &args.cargo_lock_file {
+        let (packages, git) = get_locked_packages_from_cargo_lock_file(cargo_lock_file_path, &args.package)?;
+        locked_packages.extend(packages);
+        git_dependencies.extend(git);
+    }
+
+    if let Some(std_lock_file_path) = find_build_std_lock_file(&args)? {
+        let (packages, git) = get_locked_packages_from_cargo_lock_file(std_lock_file_path.as_str(), &[])?;
+        locked_packages.extend(packages);
+        git_dependencies.extend(git);
+    }
+
+    for sbom_path in &args.sbom {
+        locked_packages.extend(read_sbom(sbom_path)?);
+    }
+
+    create_output_folder(&output_path);
+
+    // Dependencies of git packages are locked as well, crates that are not in the lock file can't be collected without the index
+    let unlocked_crates = package_git_dependencies(&git_dependencies, &output_path, &TargetFilter::default())?;
+
+    if !unlocked_crates.is_empty() {
+        return Err(anyhow!(
+            "Git dependencies depend on crates that are not locked, collect them without --no-index: {}",
+            unlocked_crates.iter().map(|krate| format!("{}@{}", krate.name, krate.version_req)).join(", ")
+        ));
+    }
+
+    let packages = collect_locked_packages(&mut registries, &locked_packages, &output_path).await?;
+
+    // Download all crates in parallel, each one is verified against the checksum from Cargo.lock.
+    download_packages(packages).await?;
+
+    Ok(())
+}
+
+fn package_git_dependencies(git_dependencies: &[GitDependency], output_path: &Path, targets: &TargetFilter) -> Result<CratesToDownload> {
+    if git_dependencies.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut git_cache = GitCache::new(GitCache::default_folder()?);
+
+    collect_git_dependencies(&mut git_cache, git_dependencies, output_path, targets)
+}
+
+// The parsers take the paths as strings, paths that are not valid UTF-8 are reported instead of panicking
+fn path_to_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Path {} is not valid UTF-8", path.display()))
+}
+
+fn create_output_folder(output_path: &Path) {
+    if !output_path.try_exists().expect("Failed to check directory creation") {
+        fs::create_dir(output_path)
+            .unwrap_or_else(|_| panic!("Failed to create output directory at {:?}", output_path));
+    }
+}
+
+// Resolve the dependencies only from the crates in previous collection, without using the index
+fn run_resolve_from_local_folder(args: Cli, folder: &Path) -> Result<()> {
+    if args.command.is_some() {
+        return Err(anyhow!("--resolve-from is not supported with subcommands"));
+    }
+
+    // Without version requirement take the highest version that exists locally
+    let mut crates_to_download: CratesToDownload = get_crate_specs(&args)?
+        .into_iter()
+        .map(|spec| CrateToDownload::new(spec.name, spec.version_req.unwrap_or("*".to_string())))
+        .collect();
+
+    let (crates_from_files, git_dependencies) = get_crate_names_and_versions_from_files(&args, &TargetFilter::from_targets(&args.target)?)?;
+    crates_to_download.extend(crates_from_files);
+
+    for git_dependency in git_dependencies {
+        warn!("Git dependency {} ({}) is not resolved from the local folder", git_dependency.name, git_dependency.source);
+    }
+
+    let resolution = resolve_from_local_folder(folder, &mut crates_to_download)?;
+
+    if resolution.missing.is_empty() {
+        info!("All dependencies exist in {}", folder.display());
+        return Ok(());
+    }
+
+    info!("Missing crates that need to be collected:");
+
+    for missing in resolution.missing {
+        println!("{}@{}", missing.name, missing.version_req);
+    }
+
+    Ok(())
+}
+
+// The standard library dependencies are collected from its Cargo.lock like any other lock file,
+// all of its packages are taken since `-p` chooses the members of the project inputs
+fn find_build_std_lock_file(args: &Cli) -> Result<Option<String>> {
+    let Some(toolchain) = &args.build_std else {
+        return Ok(None);
+    };
+
+    let std_lock_file = find_std_lock_file(find_sysroot(toolchain.as_deref())?.as_path())?;
+    info!("Collecting the dependencies of the standard library from {}", std_lock_file.display());
+
+    let std_lock_file = std_lock_file
+        .to_str()
+        .ok_or_else(|| anyhow!("Cargo.lock of the standard library at {} is not valid UTF-8 path", std_lock_file.display()))?;
+
+    Ok(Some(std_lock_file.to_string()))
 }
 
-fn get_version_requirements_for_crate(index: &GitIndex, crate_name: String) -> Result<String> {
+// Crates from `--crate-name`, the positional crates and the list files
+fn get_crate_specs(args: &Cli) -> Result<Vec<CrateSpec>> {
+    let mut specs = args.crate_specs()?;
+
+    for list_file_path in &args.from_list {
+        specs.extend(read_crates_list(list_file_path)?);
+    }
+
+    Ok(specs)
+}
+
+// Crates and git dependencies from all the manifest, lock file and archive inputs
+fn get_crate_names_and_versions_from_files(args: &Cli, targets: &TargetFilter) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let mut inputs = vec![];
+
+    for cargo_file_path in &args.cargo_file {
+        let lock_file_path = find_workspace_lock_file(Path::new(cargo_file_path));
+
+        if args.package.is_empty() {
+            if let Some(lock_file_path) = lock_file_path {
+                info!(
+                    "Workspace of {} has {}, use --cargo-lock-file to collect its exact versions",
+                    cargo_file_path,
+                    lock_file_path.display()
+                );
+            }
+
+            inputs.push(get_crate_names_and_versions_from_cargo_file(cargo_file_path.clone(), targets)?);
+        } else if let Some(lock_file_path) = lock_file_path {
+            // The chosen members are resolved with the Cargo.lock of their workspace, like `cargo build -p <member>`
+            info!("Collecting the packages of {} from {}", args.package.join(", "), lock_file_path.display());
+
+            inputs.push(get_crate_names_and_versions_from_cargo_lock_file(path_to_string(lock_file_path.as_path())?.as_str(), &args.package)?);
+        } else {
+            let deps = parse_workspace_members_from_path(Path::new(cargo_file_path), targets, &args.package)?;
+            inputs.push(cargo_file_dependencies_to_crates_to_download(deps)?);
+        }
+    }
+
+    for cargo_lock_file_path in &args.cargo_lock_file {
+        inputs.push(get_crate_names_and_versions_from_cargo_lock_file(cargo_lock_file_path, &args.package)?);
+    }
+
+    if let Some(std_lock_file_path) = find_build_std_lock_file(args)? {
+        inputs.push(get_crate_names_and_versions_from_cargo_lock_file(std_lock_file_path.as_str(), &[])?);
+    }
+
+    for cargo_metadata_path in &args.cargo_metadata {
+        inputs.push(get_crate_names_and_versions_from_cargo_metadata(cargo_metadata_path, &args.target)?);
+    }
+
+    for crate_file_path in &args.crate_file {
+        inputs.push(get_crate_names_and_versions_from_crate_file(crate_file_path, targets)?);
+    }
+
+    for sdist_path in &args.python_sdist {
+        inputs.push(get_crate_names_and_versions_from_python_sdist(sdist_path, targets)?);
+    }
+
+    for sbom_path in &args.sbom {
+        inputs.push((locked_packages_to_crates_to_download(read_sbom(sbom_path)?), vec![]));
+    }
+
+    for scan_folder_path in &args.scan {
+        let projects = scan_folder(scan_folder_path)?;
+        info!(
+            "Found {} Cargo.lock files and {} Cargo.toml files without Cargo.lock in {}",
+            projects.cargo_lock_files.len(),
+            projects.cargo_files.len(),
+            scan_folder_path.display()
+        );
+
+        // Malformed file in the tree does not fail the whole scan
+        for cargo_lock_file_path in projects.cargo_lock_files {
+            let input = path_to_string(cargo_lock_file_path.as_path())
+                .and_then(|path| get_crate_names_and_versions_from_cargo_lock_file(path.as_str(), &[]));
+
+            match input {
+                Ok(input) => inputs.push(input),
+                Err(err) => warn!("Skipped, Can't collect {}: {:#}", cargo_lock_file_path.display(), err),
+            }
+        }
+
+        for cargo_file_path in projects.cargo_files {
+            match path_to_string(cargo_file_path.as_path()).and_then(|path| get_crate_names_and_versions_from_cargo_file(path, targets)) {
+                Ok(input) => inputs.push(input),
+                Err(err) => warn!("Skipped, Can't collect {}: {:#}", cargo_file_path.display(), err),
+            }
+        }
+    }
+
+    let mut crates_to_download = vec![];
+    let mut git_dependencies = vec![];
+
+    for (crates, git) in inputs {
+        crates_to_download.extend(crates);
+        git_dependencies.extend(git);
+    }
+
+    Ok((crates_to_download, git_dependencies))
+}
+
+async fn get_crate_names_and_versions_from_specs(registry: &Registry, specs: Vec<CrateSpec>) -> Result<CratesToDownload> {
+    let mut crates_to_download = vec![];
+
+    for spec in specs {
+        // Take the version requirement from args if exists,
+        // otherwise define the highest normal version as the version req.
+        let version_req = if let Some(version_req) = spec.version_req {
+            version_req
+        } else {
+            get_version_requirements_for_crate(registry, spec.name.clone()).await?
+        };
+
+        crates_to_download.push(CrateToDownload::new(spec.name, version_req).with_features(spec.features));
+    }
+
+    Ok(crates_to_download)
+}
+
+// Without `--locked` the crate is collected like any other crate,
+// with it the packages of the Cargo.lock that is published in the crate archive are collected exactly
+async fn get_crate_names_and_versions_from_install(
+    registries: &mut Registries,
+    install: &InstallArgs,
+    output_path: &Path,
+) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let spec = &install.krate;
+
+    let version_req = match &spec.version_req {
+        Some(version_req) => version_req.clone(),
+        None => get_version_requirements_for_crate(registries.default_registry(), spec.name.clone()).await?,
+    };
+
+    let crate_to_download = CrateToDownload::new(spec.name.clone(), version_req);
+
+    if !install.locked {
+        return Ok((vec![crate_to_download], vec![]));
+    }
+
+    let (version, crate_file_path) = download_published_crates(registries, &[crate_to_download], output_path)
+        .await?
+        .remove(0);
+
+    let lock_file_content = read_file_from_crate_archive(crate_file_path.as_path(), "Cargo.lock")?
+        .ok_or_else(|| anyhow!("Crate {} {} is published without Cargo.lock, collect it without --locked", spec.name, version))?;
+
+    info!("Collecting the packages of Cargo.lock from {} {}", spec.name, version);
+
+    get_crate_names_and_versions_from_lock_file_content(lock_file_content, crate_file_path.as_path())
+}
+
+// Every package in `.crates2.json` of cargo home, from the Cargo.lock it is published with
+// or from the index when it does not have one
+async fn get_crate_names_and_versions_from_installed(
+    registries: &mut Registries,
+    output_path: &Path,
+) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let installed_file_path = default_installed_file()?;
+    let installed_packages = read_installed_packages(installed_file_path.as_path())?;
+    info!("Found {} installed packages in {}", installed_packages.len(), installed_file_path.display());
+
+    let mut crates_to_download = vec![];
+    let mut git_dependencies = vec![];
+    let mut published_crates = vec![];
+
+    for installed_package in installed_packages {
+        let index_url = match installed_package.source {
+            PackageSource::Registry(index_url) => index_url,
+            PackageSource::Git(source) => {
+                git_dependencies.push(GitDependency { name: installed_package.name, source, locked_version: None });
+                continue;
+            }
+            PackageSource::Path(path) => {
+                warn!("Skipping {} {} that was installed from local path {}", installed_package.name, installed_package.version, path);
+                continue;
+            }
+        };
+
+        published_crates.push(
+            CrateToDownload::new(installed_package.name.clone(), "=".to_owned() + installed_package.version.as_str())
+                .with_registry(Some(index_url))
+                // All the optional dependencies are collected for `--all-features`
+                .with_features((!installed_package.all_features).then_some(installed_package.features))
+                .with_default_features(!installed_package.no_default_features)
+        );
+    }
+
+    let downloaded_crates = download_published_crates(registries, &published_crates, output_path).await?;
+
+    for (crate_to_download, (version, crate_file_path)) in published_crates.into_iter().zip(downloaded_crates) {
+        match read_file_from_crate_archive(crate_file_path.as_path(), "Cargo.lock")? {
+            Some(lock_file_content) => {
+                info!("Collecting the packages of Cargo.lock from {} {}", crate_to_download.name, version);
+
+                let (crates, git) = get_crate_names_and_versions_from_lock_file_content(lock_file_content, crate_file_path.as_path())?;
+                crates_to_download.extend(crates);
+                git_dependencies.extend(git);
+            }
+            None => crates_to_download.push(crate_to_download),
+        }
+    }
+
+    Ok((crates_to_download, git_dependencies))
+}
+
+// Download the crates that match the requirements to the output folder when they are not there already,
+// return the version and the path of the crate file of each one.
+// The crates themselves are needed for reading their Cargo.lock, so they are downloaded together
+// in their own pass before the dependencies are collected
+async fn download_published_crates(
+    registries: &mut Registries,
+    crates_to_download: &[CrateToDownload],
+    output_path: &Path,
+) -> Result<Vec<(String, PathBuf)>> {
+    create_output_folder(output_path);
+
+    let mut published_crates = vec![];
+    let mut missing_packages = HashSet::new();
+
+    for crate_to_download in crates_to_download {
+        let (version, package) = find_package(registries, crate_to_download, output_path)
+            .await?
+            .ok_or_else(|| anyhow!("Crate {} not found", crate_to_download.name))?;
+
+        published_crates.push((version, package.path.clone()));
+
+        if !package.path.exists() {
+            missing_packages.insert(package);
+        }
+    }
+
+    if !missing_packages.is_empty() {
+        download_packages(missing_packages).await?;
+    }
+
+    Ok(published_crates)
+}
+
+async fn get_version_requirements_for_crate(registry: &Registry, crate_name: String) -> Result<String> {
 
     // Take the version requirement from args if exists,
     // otherwise define the highest normal version as the version req.
 
-    let krate = index
+    let krate = registry
         .crate_(&crate_name)
+        .await?
         .ok_or_else(|| anyhow!(format!("Crate {} not found", crate_name)))?;
 
-    return Ok(krate
+    Ok(krate
         .highest_normal_version()
         .unwrap_or(krate.highest_version())
         .version()
-        .to_owned());
+        .to_owned())
 }
 
 
-fn get_crate_names_and_versions_from_cargo_file(args: Cli) -> CratesToDownload {
-    let cargo_file_path = args.cargo_file.expect("Must exists");
+fn get_crate_names_and_versions_from_cargo_file(cargo_file_path: String, targets: &TargetFilter) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let deps = parse_cargo_file_from_path(cargo_file_path, targets)?;
+
+    cargo_file_dependencies_to_crates_to_download(deps)
+}
 
-    let deps = parse_cargo_file_from_path(cargo_file_path);
+// The crates carry the patches of the manifest, so the patched crates are not collected in their dependencies either
+fn cargo_file_dependencies_to_crates_to_download(deps: CargoFileDependencies) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let patches = Arc::new(get_patched_crates(&deps.replaced)?);
 
-    return deps.iter()
-        .map(|(key, _)| (key.name.clone(), key.version.clone()))
+    let crates_to_download = deps.registry
+        .keys()
+        .map(|key| CrateToDownload::new(key.name.clone(), key.version.clone()).with_patches(patches.clone()))
         .collect();
+
+    Ok((crates_to_download, deps.git.into_iter().collect()))
 }
 
-fn get_crate_names_and_versions_from_cargo_lock_file(args: Cli) -> CratesToDownload {
-    let cargo_lock_file_path = args.cargo_lock_file.expect("Must exists");
+// The source of `[patch]` is `crates-io`, the name of registry from cargo config or url
+fn get_patched_crates(replaced: &HashSet<(String, String)>) -> Result<BTreeSet<PatchedCrate>> {
+    let mut patched_crates = BTreeSet::new();
+
+    for (source, name) in replaced {
+        let registry = if source == CRATES_IO_REGISTRY_NAME {
+            None
+        } else if source.contains("://") {
+            Some(source.clone())
+        } else {
+            match configured_index_url(source)? {
+                Some(index_url) => Some(index_url),
+                None => {
+                    warn!("Registry {} of the patch of {} is not configured, collecting the upstream crate", source, name);
+                    continue;
+                }
+            }
+        };
+
+        patched_crates.insert(PatchedCrate { registry, name: name.clone() });
+    }
 
-    let cargo_file_content = fs::read_to_string(cargo_lock_file_path.clone()).expect(format!("Failed to read Cargo.lock file at {}", cargo_lock_file_path).as_str());
+    Ok(patched_crates)
+}
 
-    let deps = parse_cargo_lock_file(cargo_file_content);
+// Cargo.lock of the workspace that the cargo file belongs to, cargo resolves all the members with it
+fn find_workspace_lock_file(cargo_file_path: &Path) -> Option<PathBuf> {
+    let canonical_path = canonicalize_cargo_file_path(cargo_file_path).ok()?;
+    let cargo_file_folder = canonical_path.parent()?;
+    let cargo = read_cargo_file(canonical_path.as_path()).ok()?;
+
+    let workspace_folder = match &cargo.workspace {
+        Some(_) => cargo_file_folder.to_path_buf(),
+        None => match find_workspace_root(cargo_file_folder, &cargo) {
+            Some((workspace_folder, _)) => workspace_folder,
+            None => cargo_file_folder.to_path_buf(),
+        },
+    };
 
-    if deps.package.is_none() {
-        return vec![];
+    let lock_file_path = workspace_folder.join("Cargo.lock");
+
+    lock_file_path.is_file().then_some(lock_file_path)
+}
+
+// Take the dependencies of `.crate` file from its Cargo.lock, or from its Cargo.toml when it does not have one
+fn get_crate_names_and_versions_from_crate_file(crate_file_path: &Path, targets: &TargetFilter) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    if let Some(lock_file_content) = read_file_from_crate_archive(crate_file_path, "Cargo.lock")? {
+        info!("Collecting the packages of Cargo.lock from {}", crate_file_path.display());
+
+        return get_crate_names_and_versions_from_lock_file_content(lock_file_content, crate_file_path);
     }
 
-    return deps
-        .package
-        .unwrap()
-        .iter()
+    let cargo_file_content = read_file_from_crate_archive(crate_file_path, "Cargo.toml")?
+        .ok_or_else(|| anyhow!("Crate file {} does not have Cargo.toml", crate_file_path.display()))?;
 
-        // Only take the packages that are not local packages (local packages does not have source
-        .filter(|package| package.source.is_some())
-        // In lock file we want exact version
-        .map(|package| (package.name.clone(), "=".to_owned() + package.version.clone().as_str()))
-        .collect();
+    // The Cargo.toml is parsed from disk, so it is extracted to temporary folder that is removed on drop
+    let extract_folder = tempdir::TempDir::new("cargo-collect").context("Failed to create temporary folder")?;
+
+    let cargo_file_path = extract_folder.path().join("Cargo.toml");
+    fs::write(&cargo_file_path, cargo_file_content)?;
+
+    get_crate_names_and_versions_from_cargo_file(path_to_string(cargo_file_path.as_path())?, targets)
+}
+
+// Take the dependencies of the Rust extensions of python sdist, from their Cargo.lock or their Cargo.toml when there is none
+fn get_crate_names_and_versions_from_python_sdist(sdist_path: &Path, targets: &TargetFilter) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let sdist = PythonSdist::extract(sdist_path)?;
+
+    let mut crates_to_download = vec![];
+    let mut git_dependencies = vec![];
+    let mut visited_lock_files = HashSet::new();
+
+    for cargo_files in sdist.find_cargo_files()? {
+        let (crates, git) = match cargo_files.cargo_lock_file {
+            Some(cargo_lock_file) => {
+                // Workspace members share the same lock file
+                if !visited_lock_files.insert(cargo_lock_file.clone()) {
+                    continue;
+                }
+
+                info!("Collecting the packages of {} from python sdist", cargo_lock_file.display());
+
+                let lock_file_content = fs::read_to_string(&cargo_lock_file)
+                    .with_context(|| format!("Failed to read Cargo.lock file at {}", cargo_lock_file.display()))?;
+
+                get_crate_names_and_versions_from_lock_file_content(lock_file_content, cargo_lock_file.as_path())?
+            }
+            None => {
+                info!("Collecting the dependencies of {} from python sdist", cargo_files.cargo_file.display());
+
+                get_crate_names_and_versions_from_cargo_file(path_to_string(cargo_files.cargo_file.as_path())?, targets)?
+            }
+        };
+
+        crates_to_download.extend(crates);
+        git_dependencies.extend(git);
+    }
+
+    Ok((crates_to_download, git_dependencies))
+}
+
+fn get_crate_names_and_versions_from_lock_file_content(lock_file_content: String, path: &Path) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let deps = parse_cargo_lock_file(lock_file_content)
+        .with_context(|| format!("Failed to parse Cargo.lock from {}", path.display()))?;
+
+    let (locked_packages, git_dependencies) = split_packages_by_source(deps.package.unwrap_or_default())?;
+
+    Ok((locked_packages_to_crates_to_download(locked_packages), git_dependencies))
+}
+
+fn get_crate_names_and_versions_from_cargo_lock_file(cargo_lock_file_path: &str, members: &[String]) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let (locked_packages, git_dependencies) = get_locked_packages_from_cargo_lock_file(cargo_lock_file_path, members)?;
+
+    Ok((locked_packages_to_crates_to_download(locked_packages), git_dependencies))
+}
+
+fn get_crate_names_and_versions_from_cargo_metadata(cargo_metadata_path: &Path, targets: &[String]) -> Result<(CratesToDownload, Vec<GitDependency>)> {
+    let metadata = read_cargo_metadata(cargo_metadata_path, targets)?;
+    let (locked_packages, git_dependencies) = split_packages_by_source(metadata.resolved_packages()?)?;
+
+    Ok((locked_packages_to_crates_to_download(locked_packages), git_dependencies))
+}
+
+fn locked_packages_to_crates_to_download(locked_packages: Vec<LockedPackage>) -> CratesToDownload {
+    locked_packages
+        .into_iter()
+        .map(|package| {
+            // In lock file we want exact version
+            CrateToDownload::new(package.name, "=".to_owned() + package.version.as_str())
+                .with_registry(Some(package.registry))
+                .with_checksum(package.checksum)
+        })
+        .collect()
+}
+
+// Packages of Cargo.lock, only the ones that the members depend on when there are members
+fn get_locked_packages_from_cargo_lock_file(cargo_lock_file_path: &str, members: &[String]) -> Result<(Vec<LockedPackage>, Vec<GitDependency>)> {
+    let cargo_file_content = fs::read_to_string(cargo_lock_file_path)
+        .with_context(|| format!("Failed to read Cargo.lock file at {}", cargo_lock_file_path))?;
+
+    let deps = parse_cargo_lock_file(cargo_file_content)
+        .with_context(|| format!("Failed to parse Cargo.lock file at {}", cargo_lock_file_path))?;
+
+    let packages = deps.package.unwrap_or_default();
+
+    if members.is_empty() {
+        return split_packages_by_source(packages);
+    }
+
+    let closure = LockFileGraph::new(packages)
+        .and_then(|graph| graph.members_closure(members))
+        .with_context(|| format!("Failed to take the packages of {} from {}", members.join(", "), cargo_lock_file_path))?;
+
+    split_packages_by_source(closure)
+}
+
+// Split the resolved packages to registry packages and git dependencies
+fn split_packages_by_source(packages: Vec<LockFilePackage>) -> Result<(Vec<LockedPackage>, Vec<GitDependency>)> {
+    let mut locked_packages = vec![];
+    let mut git_dependencies = vec![];
+
+    // Local packages does not have source
+    for package in packages.into_iter().filter(|package| package.source.is_some()) {
+        let source: PackageSource = package.source.as_ref().unwrap().parse()?;
+
+        match source {
+            PackageSource::Registry(index_url) => locked_packages.push(LockedPackage {
+                name: package.name,
+                version: package.version,
+                registry: index_url,
+                checksum: package.checksum,
+            }),
+            PackageSource::Git(git_source) => git_dependencies.push(GitDependency {
+                name: package.name,
+                source: git_source,
+                locked_version: Some(package.version),
+            }),
+            PackageSource::Path(_) => {}
+        }
+    }
+
+    Ok((locked_packages, git_dependencies))
 }
 
 #[tokio::main(flavor = "multi_thread")]
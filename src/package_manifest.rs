@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use toml::{Table, Value};
+
+use crate::parse_cargo_files::cargo_toml_file::{find_workspace_root, get_package_name_and_version, CargoToml};
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+// Cargo.toml of package the way `cargo package` writes it, so the crate file does not need its workspace
+#[derive(Debug, PartialEq)]
+pub struct NormalizedManifest {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) content: String,
+    // Cargo.toml of the path dependencies, they are packaged as crates of their own
+    pub(crate) path_dependencies: Vec<PathBuf>,
+}
+
+// Inline the fields and the dependencies that are inherited from the workspace
+// and turn the path dependencies into version dependencies
+pub fn normalize_manifest(cargo_file_path: &Path) -> Result<NormalizedManifest> {
+    let cargo_file_folder = cargo_file_path.parent().expect("cargo file path must be inside a directory");
+    let content = fs::read_to_string(cargo_file_path)
+        .with_context(|| format!("Failed to read Cargo.toml file at {}", cargo_file_path.display()))?;
+
+    let mut manifest: Table = content.parse()
+        .with_context(|| format!("Failed to parse Cargo.toml file at {}", cargo_file_path.display()))?;
+    let cargo: CargoToml = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse Cargo.toml file at {}", cargo_file_path.display()))?;
+
+    let (name, version) = get_package_name_and_version(cargo_file_path)
+        .ok_or_else(|| anyhow!("{} does not have [package] with version", cargo_file_path.display()))?;
+
+    // The root package of the workspace is its own workspace root
+    let workspace = match manifest.remove("workspace") {
+        Some(Value::Table(workspace)) => Some((cargo_file_folder.to_path_buf(), workspace)),
+        _ => read_workspace_root(cargo_file_folder, &cargo)?,
+    };
+
+    if let Some(Value::Table(package)) = manifest.get_mut("package") {
+        package.remove("workspace");
+        inherit_package_fields(package, workspace.as_ref())?;
+    }
+
+    if is_inherited(manifest.get("lints")) {
+        let lints = workspace_table(workspace.as_ref(), "lints")?.clone();
+        manifest.insert("lints".to_string(), Value::Table(lints));
+    }
+
+    let mut path_dependencies = vec![];
+
+    normalize_dependency_tables(&mut manifest, cargo_file_folder, workspace.as_ref(), &mut path_dependencies)?;
+
+    if let Some(Value::Table(targets)) = manifest.get_mut("target") {
+        for (_, target) in targets.iter_mut() {
+            if let Value::Table(target) = target {
+                normalize_dependency_tables(target, cargo_file_folder, workspace.as_ref(), &mut path_dependencies)?;
+            }
+        }
+    }
+
+    Ok(NormalizedManifest {
+        name,
+        version,
+        content: toml::to_string(&manifest)?,
+        path_dependencies,
+    })
+}
+
+// Folder and `[workspace]` table of the workspace root of the member
+fn read_workspace_root(member_folder: &Path, member: &CargoToml) -> Result<Option<(PathBuf, Table)>> {
+    let Some((workspace_folder, _)) = find_workspace_root(member_folder, member) else {
+        return Ok(None);
+    };
+
+    let workspace_cargo_file_path = workspace_folder.join("Cargo.toml");
+    let root: Table = fs::read_to_string(&workspace_cargo_file_path)
+        .with_context(|| format!("Failed to read Cargo.toml file at {}", workspace_cargo_file_path.display()))?
+        .parse()
+        .with_context(|| format!("Failed to parse Cargo.toml file at {}", workspace_cargo_file_path.display()))?;
+
+    match root.get("workspace") {
+        Some(Value::Table(workspace)) => Ok(Some((workspace_folder, workspace.clone()))),
+        _ => Ok(None),
+    }
+}
+
+fn workspace_table<'a>(workspace: Option<&'a (PathBuf, Table)>, key: &str) -> Result<&'a Table> {
+    let (workspace_folder, workspace) = workspace.ok_or_else(|| anyhow!("Failed to find the workspace root to inherit [workspace.{}] from", key))?;
+
+    match workspace.get(key) {
+        Some(Value::Table(table)) => Ok(table),
+        _ => Err(anyhow!("{} does not have [workspace.{}]", workspace_folder.join("Cargo.toml").display(), key)),
+    }
+}
+
+// `{ workspace = true }`
+fn is_inherited(value: Option<&Value>) -> bool {
+    matches!(value, Some(Value::Table(table)) if table.get("workspace") == Some(&Value::Boolean(true)))
+}
+
+// Replace `field.workspace = true` of `[package]` with the field from `[workspace.package]`
+fn inherit_package_fields(package: &mut Table, workspace: Option<&(PathBuf, Table)>) -> Result<()> {
+    let inherited_fields = package
+        .iter()
+        .filter(|(_, value)| is_inherited(Some(value)))
+        .map(|(field, _)| field.clone())
+        .collect::<Vec<_>>();
+
+    for field in inherited_fields {
+        let value = workspace_table(workspace, "package")?
+            .get(&field)
+            .ok_or_else(|| anyhow!("Field {} is inherited from the workspace but missing in [workspace.package]", field))?;
+
+        package.insert(field, value.clone());
+    }
+
+    Ok(())
+}
+
+fn normalize_dependency_tables(
+    table: &mut Table,
+    cargo_file_folder: &Path,
+    workspace: Option<&(PathBuf, Table)>,
+    path_dependencies: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for dependency_table in DEPENDENCY_TABLES {
+        let Some(Value::Table(dependencies)) = table.get_mut(dependency_table) else {
+            continue;
+        };
+
+        let mut normalized = Table::new();
+
+        for (name, dependency) in std::mem::take(dependencies) {
+            let (dependency, folder) = if is_inherited(Some(&dependency)) {
+                let workspace_dependency = workspace_table(workspace, "dependencies")?
+                    .get(&name)
+                    .ok_or_else(|| anyhow!("Dependency {} is inherited from the workspace but missing in [workspace.dependencies]", name))?;
+                let (workspace_folder, _) = workspace.expect("Workspace has [workspace.dependencies]");
+
+                (inherit_dependency(&dependency, workspace_dependency), workspace_folder.as_path())
+            } else {
+                (dependency, cargo_file_folder)
+            };
+
+            let Value::Table(mut detail) = dependency else {
+                normalized.insert(name, dependency);
+                continue;
+            };
+
+            if let Some(path) = detail.remove("path") {
+                let path = path.as_str().ok_or_else(|| anyhow!("Path of dependency {} is not a string", name))?;
+                let dependency_cargo_file_path = folder.join(path).join("Cargo.toml");
+
+                // Like `cargo package`, dev dependencies without version are not published
+                if dependency_table == "dev-dependencies" && !detail.contains_key("version") {
+                    continue;
+                }
+
+                if !detail.contains_key("version") {
+                    let (_, version) = get_package_name_and_version(dependency_cargo_file_path.as_path())
+                        .ok_or_else(|| anyhow!("Failed to read the version of path dependency {} at {}", name, dependency_cargo_file_path.display()))?;
+
+                    detail.insert("version".to_string(), Value::String(version));
+                }
+
+                path_dependencies.push(dependency_cargo_file_path);
+            }
+
+            normalized.insert(name, Value::Table(detail));
+        }
+
+        *dependencies = normalized;
+    }
+
+    Ok(())
+}
+
+// The dependency from `[workspace.dependencies]` with the features and `optional` of the member
+fn inherit_dependency(member_dependency: &Value, workspace_dependency: &Value) -> Value {
+    let mut detail = match workspace_dependency {
+        Value::Table(detail) => detail.clone(),
+        version => Table::from_iter([("version".to_string(), version.clone())]),
+    };
+
+    let Value::Table(member_detail) = member_dependency else {
+        return Value::Table(detail);
+    };
+
+    if let Some(Value::Array(member_features)) = member_detail.get("features") {
+        let features = detail
+            .entry("features")
+            .or_insert_with(|| Value::Array(vec![]));
+
+        if let Value::Array(features) = features {
+            for feature in member_features {
+                if !features.contains(feature) {
+                    features.push(feature.clone());
+                }
+            }
+        }
+    }
+
+    if let Some(optional) = member_detail.get("optional") {
+        detail.insert("optional".to_string(), optional.clone());
+    }
+
+    Value::Table(detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn write_files(folder: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let file_path = folder.join(path);
+            fs::create_dir_all(file_path.parent().expect("Must have parent")).expect("Failed to create dir");
+            fs::write(file_path, content).expect("Failed to write file");
+        }
+    }
+
+    #[test]
+    fn normalize_workspace_member() {
+        let tmp_dir = tempdir::TempDir::new("package_manifest_test").expect("Failed to create temp dir");
+
+        // language=toml
+        write_files(tmp_dir.path(), &[
+            ("Cargo.toml", r#"
+[workspace]
+members = ["crates/*"]
+
+[workspace.package]
+version = "0.3.0"
+edition = "2021"
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+log = "0.4"
+common = { path = "crates/common" }
+            "#),
+            ("crates/app/Cargo.toml", r#"
+[package]
+name = "app"
+version.workspace = true
+edition.workspace = true
+
+[dependencies]
+serde = { workspace = true, features = ["rc"], optional = true }
+log.workspace = true
+common.workspace = true
+helper = { path = "../helper", version = "0.1" }
+
+[dev-dependencies]
+test-utils = { path = "../test-utils" }
+            "#),
+            ("crates/common/Cargo.toml", "[package]\nname = \"common\"\nversion.workspace = true\n"),
+            ("crates/helper/Cargo.toml", "[package]\nname = \"helper\"\nversion = \"0.1.2\"\n"),
+            ("crates/test-utils/Cargo.toml", "[package]\nname = \"test-utils\"\nversion = \"0.0.1\"\n"),
+        ]);
+
+        let manifest = normalize_manifest(tmp_dir.path().join("crates/app/Cargo.toml").as_path()).expect("Failed to normalize");
+
+        // language=toml
+        let expected: Table = r#"
+[package]
+name = "app"
+version = "0.3.0"
+edition = "2021"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive", "rc"], optional = true }
+log = { version = "0.4" }
+common = { version = "0.3.0" }
+helper = { version = "0.1" }
+
+[dev-dependencies]
+        "#.parse().unwrap();
+
+        assert_eq!((manifest.name.as_str(), manifest.version.as_str()), ("app", "0.3.0"));
+        assert_eq!(manifest.content.parse::<Table>().unwrap(), expected);
+        assert_eq!(
+            manifest.path_dependencies.iter().map(|path| path.strip_prefix(tmp_dir.path()).unwrap().to_path_buf()).collect::<Vec<_>>(),
+            vec![PathBuf::from("crates/common/Cargo.toml"), PathBuf::from("crates/app/../helper/Cargo.toml")]
+        );
+    }
+
+    #[test]
+    fn fail_for_field_missing_in_workspace() {
+        let tmp_dir = tempdir::TempDir::new("package_manifest_test").expect("Failed to create temp dir");
+
+        // language=toml
+        write_files(tmp_dir.path(), &[
+            ("Cargo.toml", "[workspace]\nmembers = [\"app\"]\n"),
+            ("app/Cargo.toml", "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense.workspace = true\n"),
+        ]);
+
+        assert_eq!(normalize_manifest(tmp_dir.path().join("app/Cargo.toml").as_path()).is_err(), true);
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::parse_cargo_files::parse_lock_file::Package;
+
+// The output of `cargo metadata --format-version 1`, only the parts that are needed for collecting
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct CargoMetadata {
+    pub packages: Vec<MetadataPackage>,
+
+    // Missing when generated with `--no-deps`
+    pub resolve: Option<MetadataResolve>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct MetadataPackage {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    // Same format as the source in Cargo.lock, missing for local packages
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct MetadataResolve {
+    pub nodes: Vec<MetadataNode>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct MetadataNode {
+    pub id: String,
+}
+
+impl CargoMetadata {
+    // The packages in the resolved dependency graph, in the same form as the packages of Cargo.lock
+    pub fn resolved_packages(&self) -> Result<Vec<Package>> {
+        let resolve = self.resolve
+            .as_ref()
+            .ok_or_else(|| anyhow!("cargo metadata does not have the resolve section, it must be generated without --no-deps"))?;
+
+        let resolved_ids = resolve.nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<HashSet<&str>>();
+
+        Ok(self.packages
+            .iter()
+            .filter(|package| resolved_ids.contains(package.id.as_str()))
+            .map(|package| Package {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                source: package.source.clone(),
+                // cargo metadata does not have checksums
+                checksum: None,
+                dependencies: None,
+            })
+            .collect())
+    }
+}
+
+pub fn parse_cargo_metadata(content: &str) -> Result<CargoMetadata> {
+    serde_json::from_str(content).context("Failed to deserialize cargo metadata")
+}
+
+// Read cargo metadata JSON file, or run `cargo metadata` when the path is Cargo.toml file.
+// The targets are passed to `cargo metadata` to resolve only the dependencies of these platforms
+pub fn read_cargo_metadata(path: &Path, targets: &[String]) -> Result<CargoMetadata> {
+    if path.file_name().is_some_and(|file_name| file_name == "Cargo.toml") {
+        return run_cargo_metadata(path, targets);
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read cargo metadata file at {}", path.display()))?;
+
+    parse_cargo_metadata(content.as_str())
+        .with_context(|| format!("Failed to parse cargo metadata file at {}", path.display()))
+}
+
+fn run_cargo_metadata(cargo_file_path: &Path, targets: &[String]) -> Result<CargoMetadata> {
+    let cargo = std::env::var("CARGO").unwrap_or("cargo".to_string());
+
+    let mut command = Command::new(cargo.as_str());
+    command
+        .args(["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(cargo_file_path);
+
+    for target in targets {
+        command.args(["--filter-platform", target.as_str()]);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {} metadata", cargo))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cargo metadata failed for {}: {}",
+            cargo_file_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    parse_cargo_metadata(String::from_utf8(output.stdout)?.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn resolved_packages_from_metadata() {
+        // language=json
+        let metadata = parse_cargo_metadata(r#"
+{
+    "packages": [
+        {
+            "name": "my-crate",
+            "version": "0.1.0",
+            "id": "path+file:///projects/my-crate#0.1.0",
+            "source": null,
+            "dependencies": []
+        },
+        {
+            "name": "serde",
+            "version": "1.0.188",
+            "id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.188",
+            "source": "registry+https://github.com/rust-lang/crates.io-index",
+            "dependencies": []
+        },
+        {
+            "name": "my-git-crate",
+            "version": "0.2.0",
+            "id": "git+https://github.com/foo/my-git-crate?branch=main#my-git-crate@0.2.0",
+            "source": "git+https://github.com/foo/my-git-crate?branch=main#0123456789abcdef",
+            "dependencies": []
+        },
+        {
+            "name": "not-resolved",
+            "version": "1.0.0",
+            "id": "registry+https://github.com/rust-lang/crates.io-index#not-resolved@1.0.0",
+            "source": "registry+https://github.com/rust-lang/crates.io-index",
+            "dependencies": []
+        }
+    ],
+    "resolve": {
+        "nodes": [
+            { "id": "path+file:///projects/my-crate#0.1.0", "dependencies": [], "deps": [], "features": [] },
+            { "id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.188", "dependencies": [], "deps": [], "features": [] },
+            { "id": "git+https://github.com/foo/my-git-crate?branch=main#my-git-crate@0.2.0", "dependencies": [], "deps": [], "features": [] }
+        ],
+        "root": "path+file:///projects/my-crate#0.1.0"
+    },
+    "version": 1
+}
+        "#).expect("Valid metadata");
+
+        assert_eq!(
+            metadata.resolved_packages().expect("Has resolve")
+                .iter()
+                .map(|package| (package.name.as_str(), package.version.as_str(), package.source.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("my-crate", "0.1.0", None),
+                ("serde", "1.0.188", Some("registry+https://github.com/rust-lang/crates.io-index")),
+                ("my-git-crate", "0.2.0", Some("git+https://github.com/foo/my-git-crate?branch=main#0123456789abcdef")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_without_resolve() {
+        let metadata = parse_cargo_metadata(r#"{ "packages": [], "resolve": null }"#).expect("Valid metadata");
+
+        assert_eq!(metadata.resolved_packages().is_err(), true);
+    }
+
+    #[test]
+    fn run_cargo_metadata_for_cargo_file() {
+        let tmp_dir = tempdir::TempDir::new("cargo_metadata_test").expect("Failed to create temp dir");
+
+        // language=toml
+        fs::write(tmp_dir.path().join("Cargo.toml"), r#"
+[package]
+name = "metadata-test"
+version = "0.1.0"
+edition = "2021"
+        "#).expect("Failed to write file");
+        fs::create_dir(tmp_dir.path().join("src")).expect("Failed to create dir");
+        fs::write(tmp_dir.path().join("src/lib.rs"), "").expect("Failed to write file");
+
+        let metadata = read_cargo_metadata(tmp_dir.path().join("Cargo.toml").as_path(), &[]).expect("cargo metadata succeeded");
+
+        assert_eq!(
+            metadata.resolved_packages().expect("Has resolve").iter().map(|package| package.name.as_str()).collect::<Vec<_>>(),
+            vec!["metadata-test"]
+        );
+    }
+}
@@ -1,15 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::hash::Hash;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::{anyhow, Context, Result};
 use derive_builder::Builder;
 use itertools::Either::{Left, Right};
 use itertools::Itertools;
 use serde::Deserialize;
+use tracing::warn;
+
+use crate::git_dependencies::GitDependency;
+use crate::parse_cargo_files::package_source::{GitReference, GitSource};
+use crate::target_filter::TargetFilter;
 
 type DependencyMap = HashMap<String, Dependency>;
 type SpecificVersionDependencyMap = HashMap<SpecificDependencyVersion, Dependency>;
+// Replacements by the source and the name of the crate they replace
+type ReplacementMap = HashMap<(String, String), Dependency>;
+
+const CRATES_IO: &str = "crates-io";
+const CRATES_IO_INDEXES: [&str; 2] = ["https://github.com/rust-lang/crates.io-index", "sparse+https://index.crates.io"];
 
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
@@ -20,9 +31,39 @@ pub struct SpecificDependencyVersion {
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct CargoToml {
+    // Missing in virtual workspace manifest
+    #[allow(dead_code)]
+    pub package: Option<Package>,
+
+    #[allow(dead_code)]
+    pub workspace: Option<Workspace>,
+
+    #[allow(dead_code)]
+    pub dependencies: Option<DependencyMap>,
+
+    #[allow(dead_code)]
+    #[serde(rename = "dev-dependencies")]
+    pub dev_dependencies: Option<DependencyMap>,
+
+    #[allow(dead_code)]
+    #[serde(rename = "build-dependencies")]
+    pub build_dependencies: Option<DependencyMap>,
+
+    // Dependencies of specific platform, the key is target triple or `cfg(...)` expression
+    #[allow(dead_code)]
+    pub target: Option<HashMap<String, TargetDependencies>>,
+
+    // Crates that replace the crates of registry or git source, the key is the registry name (e.g. `crates-io`) or the source url
+    #[allow(dead_code)]
+    pub patch: Option<HashMap<String, DependencyMap>>,
+
+    // Older way to replace crates, the key is `name:version`
     #[allow(dead_code)]
-    pub package: Package,
+    pub replace: Option<DependencyMap>,
+}
 
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct TargetDependencies {
     #[allow(dead_code)]
     pub dependencies: Option<DependencyMap>,
 
@@ -39,6 +80,58 @@ pub struct CargoToml {
 pub struct Package {
     #[allow(dead_code)]
     pub name: String,
+
+    #[allow(dead_code)]
+    pub version: Option<PackageVersion>,
+
+    // Path of the workspace root, when it is not the closest parent workspace
+    #[allow(dead_code)]
+    pub workspace: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum PackageVersion {
+    Version(String),
+    // `version.workspace = true`
+    Inherited { workspace: bool },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct WorkspacePackage {
+    // Version that members inherit with `version.workspace = true`
+    #[allow(dead_code)]
+    pub version: Option<String>,
+}
+
+// Dependencies of Cargo.toml file and the local Cargo.toml files it depends on
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CargoFileDependencies {
+    pub registry: SpecificVersionDependencyMap,
+    pub git: HashSet<GitDependency>,
+    // Source and name of the crates that `[patch]` or `[replace]` replace, they are not collected in the dependencies of the crates either
+    pub replaced: HashSet<(String, String)>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Workspace {
+    // Paths of the members, can be globs
+    #[allow(dead_code)]
+    pub members: Option<Vec<String>>,
+
+    #[allow(dead_code)]
+    pub exclude: Option<Vec<String>>,
+
+    #[allow(dead_code)]
+    pub default_members: Option<Vec<String>>,
+
+    // Dependencies that members inherit with `workspace = true`
+    #[allow(dead_code)]
+    pub dependencies: Option<DependencyMap>,
+
+    #[allow(dead_code)]
+    pub package: Option<WorkspacePackage>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
@@ -67,47 +160,601 @@ pub struct DependencyDetail {
     #[allow(dead_code)]
     #[builder(setter(into, strip_option), default)]
     pub path: Option<String>,
+
+    // The real crate name when the dependency is renamed
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub package: Option<String>,
+
+    // The dependency is inherited from `[workspace.dependencies]`
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub workspace: Option<bool>,
+
+    // Url of git repository that the dependency is taken from, with optional reference
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub git: Option<String>,
+
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub branch: Option<String>,
+
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub tag: Option<String>,
+
+    #[allow(dead_code)]
+    #[builder(setter(into, strip_option), default)]
+    pub rev: Option<String>,
+
+    // Name of the alternative registry, or the url of its index with `registry-index`, crates.io when missing
+    #[allow(dead_code)]
+    #[serde(alias = "registry-index")]
+    #[builder(setter(into, strip_option), default)]
+    pub registry: Option<String>,
+}
+
+pub(crate) fn parse_cargo_file(content: String) -> Result<CargoToml> {
+    let cargo_toml: CargoToml = toml::from_str(&content)?;
+
+    Ok(cargo_toml)
+}
+
+// Read and parse Cargo.toml file, the errors name the file
+pub(crate) fn read_cargo_file(cargo_file_path: &Path) -> Result<CargoToml> {
+    let cargo_file_content = fs::read_to_string(cargo_file_path)
+        .with_context(|| format!("Failed to read Cargo.toml file at {}", cargo_file_path.display()))?;
+
+    parse_cargo_file(cargo_file_content)
+        .with_context(|| format!("Failed to parse Cargo.toml file at {}", cargo_file_path.display()))
+}
+
+// Parse cargo file from path with support for local dependencies and workspace members,
+// only the target specific dependencies of the platforms in the filter are taken
+pub fn parse_cargo_file_from_path(cargo_file_path: String, targets: &TargetFilter) -> Result<CargoFileDependencies> {
+    let cargo_file_path = PathBuf::from(cargo_file_path);
+    let mut visited = HashSet::new();
+    let mut dependencies = CargoFileDependencies::default();
+
+    parse_cargo_file_from_path_once(cargo_file_path.clone(), targets, &mut visited, &mut dependencies)?;
+
+    // Workspace root collects the dependencies of all its members
+    let cargo_file_folder = cargo_file_path.parent().expect("cargo file path must be inside a directory");
+
+    if let Some(workspace) = read_cargo_file(cargo_file_path.as_path())?.workspace {
+        for member_folder in get_workspace_members(cargo_file_folder, &workspace)? {
+            parse_cargo_file_from_path_once(member_folder.join("Cargo.toml"), targets, &mut visited, &mut dependencies)?;
+        }
+    }
+
+    apply_replacements(cargo_file_path.as_path(), targets, &mut visited, &mut dependencies)?;
+
+    Ok(dependencies)
+}
+
+// Parse only the chosen members of the workspace that the cargo file belongs to, like `cargo build -p <member>`,
+// the cargo file can be the workspace root or any of its members
+pub fn parse_workspace_members_from_path(cargo_file_path: &Path, targets: &TargetFilter, member_names: &[String]) -> Result<CargoFileDependencies> {
+    let cargo_file_path = canonicalize_cargo_file_path(cargo_file_path)?;
+    let cargo_file_path = cargo_file_path.as_path();
+    let members = get_workspace_member_manifests(cargo_file_path)?;
+
+    let mut visited = HashSet::new();
+    let mut dependencies = CargoFileDependencies::default();
+
+    for member_name in member_names {
+        let member_cargo_file_path = members
+            .get(member_name)
+            .ok_or_else(|| anyhow!(
+                "Package {} is not a member of the workspace of {}, the members are: {}",
+                member_name,
+                cargo_file_path.display(),
+                members.keys().sorted().join(", ")
+            ))?;
+
+        parse_cargo_file_from_path_once(member_cargo_file_path.clone(), targets, &mut visited, &mut dependencies)?;
+    }
+
+    apply_replacements(cargo_file_path, targets, &mut visited, &mut dependencies)?;
+
+    Ok(dependencies)
+}
+
+// Absolute path of the cargo file, so the folder of relative `Cargo.toml` is not empty and its parent folders can be walked
+pub(crate) fn canonicalize_cargo_file_path(cargo_file_path: &Path) -> Result<PathBuf> {
+    fs::canonicalize(cargo_file_path)
+        .with_context(|| format!("Failed to find Cargo.toml file at {}", cargo_file_path.display()))
+}
+
+// Manifests of the workspace members by package name, a package outside of workspace is the only member of itself
+fn get_workspace_member_manifests(cargo_file_path: &Path) -> Result<HashMap<String, PathBuf>> {
+    let cargo_file_path = canonicalize_cargo_file_path(cargo_file_path)?;
+    let cargo_file_path = cargo_file_path.as_path();
+    let cargo_file_folder = cargo_file_path.parent().expect("cargo file path must be inside a directory");
+    let cargo = read_cargo_file(cargo_file_path)?;
+
+    let (workspace_folder, workspace) = match &cargo.workspace {
+        Some(workspace) => (cargo_file_folder.to_path_buf(), workspace.clone()),
+        None => match find_workspace_root(cargo_file_folder, &cargo) {
+            Some(root) => root,
+            None => {
+                let package = cargo.package.ok_or_else(|| anyhow!("{} does not have [package]", cargo_file_path.display()))?;
+                return Ok(HashMap::from([(package.name, cargo_file_path.to_path_buf())]));
+            }
+        },
+    };
+
+    // Root package of non virtual workspace is a member as well
+    let member_cargo_files = std::iter::once(workspace_folder.join("Cargo.toml"))
+        .chain(get_workspace_members(workspace_folder.as_path(), &workspace)?.into_iter().map(|member_folder| member_folder.join("Cargo.toml")));
+
+    Ok(member_cargo_files
+        .filter_map(|member_cargo_file| {
+            let (name, _) = get_package_name_and_version(member_cargo_file.as_path())?;
+            Some((name, member_cargo_file))
+        })
+        .collect())
+}
+
+// Substitute the crates from `[patch]` and `[replace]` of the workspace root,
+// the dependencies of the replacements are collected instead of the dependencies of the upstream crates.
+// Only the crates of this manifest from the same source are substituted, other inputs keep their crates,
+// the replaced crates are kept so they are substituted in the dependencies of the registry crates as well
+fn apply_replacements(
+    cargo_file_path: &Path,
+    targets: &TargetFilter,
+    visited: &mut HashSet<PathBuf>,
+    dependencies: &mut CargoFileDependencies,
+) -> Result<()> {
+    let (root_folder, replacements) = get_replacements(cargo_file_path)?;
+
+    if replacements.is_empty() {
+        return Ok(());
+    }
+
+    let mut replaced = HashSet::new();
+    let mut replacement_dependencies = CargoFileDependencies::default();
+
+    for ((source, name), replacement) in replacements {
+        match &replacement {
+            Dependency::Detailed(detail) if detail.path.is_some() => {
+                let path = detail.path.as_ref().expect("Must have path");
+                parse_cargo_file_from_path_once(root_folder.join(path).join("Cargo.toml"), targets, visited, &mut replacement_dependencies)?;
+            }
+            Dependency::Detailed(detail) if detail.git.is_some() => {
+                replacement_dependencies.git.insert(get_git_dependency(name.as_str(), detail));
+            }
+            // Replacement from other registry, the upstream crate is collected instead
+            _ => {
+                warn!("Replacement of {} is not from path or git, collecting the upstream crate", name);
+                continue;
+            }
+        }
+
+        replaced.insert((source, name));
+    }
+
+    dependencies.registry.extend(replacement_dependencies.registry);
+
+    // Replacements can depend on other patched crates as well
+    dependencies.registry.retain(|key, dep| !replaced.contains(&(get_dependency_source(dep), key.name.clone())));
+    dependencies.git.retain(|git_dependency| !replaced.contains(&(normalize_source(git_dependency.source.url.as_str()), git_dependency.name.clone())));
+    dependencies.git.extend(replacement_dependencies.git);
+    dependencies.replaced.extend(replaced);
+
+    Ok(())
+}
+
+// The `[patch]` and `[replace]` entries by source and crate name with the folder their paths are relative to,
+// cargo takes them only from the workspace root
+fn get_replacements(cargo_file_path: &Path) -> Result<(PathBuf, ReplacementMap)> {
+    let cargo_file_folder = cargo_file_path.parent().expect("cargo file path must be inside a directory");
+    let cargo = read_cargo_file(cargo_file_path)?;
+
+    let (root_folder, root) = match (&cargo.workspace, find_workspace_root(cargo_file_folder, &cargo)) {
+        (None, Some((workspace_folder, _))) => {
+            let root = read_cargo_file(workspace_folder.join("Cargo.toml").as_path())?;
+            (workspace_folder, root)
+        }
+        _ => (cargo_file_folder.to_path_buf(), cargo),
+    };
+
+    // The key of `[patch]` is `crates-io`, the name of the registry or the url of the source
+    let patches = root.patch
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(source, deps)| deps
+            .into_iter()
+            .map(move |(name, dep)| ((normalize_source(source.as_str()), get_replaced_crate_name(name.as_str(), &dep)), dep)));
+
+    let replaces = root.replace
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(spec, dep)| (parse_package_id_spec(spec.as_str()), dep));
+
+    Ok((root_folder, patches.chain(replaces).collect()))
+}
+
+// Source and name of package id spec, `name:version`, `name@version` or `url#name@version`
+fn parse_package_id_spec(spec: &str) -> (String, String) {
+    let Some((url, fragment)) = spec.split_once('#') else {
+        return (CRATES_IO.to_string(), spec.split([':', '@']).next().unwrap_or_default().to_string());
+    };
+
+    let url = url.trim_start_matches("registry+").trim_start_matches("git+");
+    let name = fragment.split([':', '@']).next().unwrap_or_default();
+
+    // `url#version` names the crate by the last segment of the url
+    let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        url.trim_end_matches('/').rsplit('/').next().unwrap_or_default().trim_end_matches(".git")
+    } else {
+        name
+    };
+
+    (normalize_source(url), name.to_string())
+}
+
+// Source of the dependency the way `[patch]` names it
+fn get_dependency_source(dep: &Dependency) -> String {
+    match dep {
+        Dependency::Detailed(DependencyDetail { git: Some(url), .. }) => normalize_source(url),
+        Dependency::Detailed(DependencyDetail { registry: Some(registry), .. }) => normalize_source(registry),
+        _ => CRATES_IO.to_string(),
+    }
 }
 
-fn parse_cargo_file(content: String) -> CargoToml {
-    let cargo_toml: CargoToml = toml::from_str(&content).expect("Failed to deserialize Cargo.toml");
+// Same source can be written with or without trailing `/` and `.git`, the index of crates.io is `crates-io`
+fn normalize_source(source: &str) -> String {
+    let source = source.trim_end_matches('/');
+    let source = source.strip_suffix(".git").unwrap_or(source);
+
+    if CRATES_IO_INDEXES.contains(&source) {
+        CRATES_IO.to_string()
+    } else {
+        source.to_string()
+    }
+}
 
-    return cargo_toml;
+// Patch entry can be renamed with `package`
+fn get_replaced_crate_name(name: &str, dep: &Dependency) -> String {
+    match dep {
+        Dependency::Detailed(DependencyDetail { package: Some(package), .. }) => package.clone(),
+        _ => name.to_string(),
+    }
 }
 
-// Parse cargo file from path with support for local dependencies
-pub fn parse_cargo_file_from_path(cargo_file_path: String) -> SpecificVersionDependencyMap {
-    let cargo_file_path_buf = PathBuf::from(cargo_file_path.clone());
-    let cargo_file_folder = cargo_file_path_buf.parent().expect("cargo file path must be inside a directory");
-    let cargo_file_content = fs::read_to_string(cargo_file_path.clone()).expect(format!("Failed to read Cargo.toml file at {}", cargo_file_path).as_str());
+// Each manifest is parsed once, so path dependencies between workspace members are not followed again
+fn parse_cargo_file_from_path_once(
+    cargo_file_path: PathBuf,
+    targets: &TargetFilter,
+    visited: &mut HashSet<PathBuf>,
+    dependencies: &mut CargoFileDependencies,
+) -> Result<()> {
+    let canonical_path = fs::canonicalize(&cargo_file_path).unwrap_or_else(|_| cargo_file_path.clone());
+
+    if !visited.insert(canonical_path) {
+        return Ok(());
+    }
+
+    let cargo_file_folder = cargo_file_path.parent().expect("cargo file path must be inside a directory");
 
-    let parsed_cargo = parse_cargo_file(cargo_file_content);
+    let mut parsed_cargo = read_cargo_file(cargo_file_path.as_path())?;
+    merge_target_dependencies(&mut parsed_cargo, targets);
+    inherit_workspace_dependencies(&mut parsed_cargo, cargo_file_folder)
+        .with_context(|| format!("Failed to inherit the workspace dependencies of {}", cargo_file_path.display()))?;
 
-    let (mut all_deps_specific, path_deps) = get_deps_maps_from_cargo(parsed_cargo);
+    let (all_deps_specific, local_deps) = get_deps_maps_from_cargo(parsed_cargo);
 
-    path_deps.iter().for_each(|(key, val)| {
+    dependencies.registry.extend(all_deps_specific);
+
+    for (key, val) in local_deps.iter() {
         match val {
             Dependency::Version(_) => unreachable!("Should not reach here"),
             Dependency::Detailed(detail) => {
-                let path = detail.clone().path.expect("Must have path");
+                if let Some(path) = detail.path.clone() {
+                    let path = cargo_file_folder.join(path);
+
+                    let file_path = path.join("Cargo.toml");
+
+                    // Add nested paths deps
+                    parse_cargo_file_from_path_once(file_path, targets, visited, dependencies)?;
+                } else {
+                    dependencies.git.insert(get_git_dependency(key, detail));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_git_dependency(name: &str, detail: &DependencyDetail) -> GitDependency {
+    let reference = detail.branch.clone().map(GitReference::Branch)
+        .or(detail.tag.clone().map(GitReference::Tag))
+        .or(detail.rev.clone().map(GitReference::Rev));
+
+    GitDependency {
+        name: detail.package.clone().unwrap_or(name.to_string()),
+        source: GitSource {
+            url: detail.git.clone().expect("Must have git"),
+            reference,
+            precise: None,
+        },
+        locked_version: None,
+    }
+}
 
-                let path = cargo_file_folder.join(path);
+// Name and version of the package in Cargo.toml, None for virtual workspace manifest
+pub(crate) fn get_package_name_and_version(cargo_file_path: &Path) -> Option<(String, String)> {
+    let cargo_file_content = fs::read_to_string(cargo_file_path).ok()?;
+    let cargo = toml::from_str::<CargoToml>(&cargo_file_content).ok()?;
+    let package = cargo.package.clone()?;
+
+    let version = match package.version {
+        Some(PackageVersion::Version(version)) => version,
+        Some(PackageVersion::Inherited { .. }) => cargo.workspace
+            .clone()
+            .or_else(|| Some(find_workspace_root(cargo_file_path.parent()?, &cargo)?.1))?
+            .package?
+            .version?,
+        // Cargo defaults to 0.0.0 when the version is missing
+        None => "0.0.0".to_string(),
+    };
+
+    Some((package.name, version))
+}
 
-                let file_path = path.join("Cargo.toml");
+// Move the dependencies of the platforms that match the filter to the top level dependencies tables
+pub(crate) fn merge_target_dependencies(cargo: &mut CargoToml, targets: &TargetFilter) {
+    for (platform, target_deps) in cargo.target.take().unwrap_or_default() {
+        if !targets.matches(platform.as_str()) {
+            continue;
+        }
 
-                // Add nested paths deps
-                all_deps_specific.extend(parse_cargo_file_from_path(file_path.to_str().expect("Failed to convert path to string").to_string()))
+        for (deps, target_deps) in [
+            (&mut cargo.dependencies, target_deps.dependencies),
+            (&mut cargo.dev_dependencies, target_deps.dev_dependencies),
+            (&mut cargo.build_dependencies, target_deps.build_dependencies),
+        ] {
+            if let Some(target_deps) = target_deps {
+                deps.get_or_insert_with(HashMap::new).extend(target_deps);
             }
         }
+    }
+}
+
+// Replace the dependencies with `workspace = true` by their entry in `[workspace.dependencies]` of the workspace root
+fn inherit_workspace_dependencies(cargo: &mut CargoToml, cargo_file_folder: &Path) -> Result<()> {
+    let is_inherited = |dep: &Dependency| matches!(dep, Dependency::Detailed(detail) if detail.workspace == Some(true));
+
+    let has_inherited = [&cargo.dependencies, &cargo.dev_dependencies, &cargo.build_dependencies]
+        .into_iter()
+        .flatten()
+        .any(|deps| deps.values().any(is_inherited));
+
+    if !has_inherited {
+        return Ok(());
+    }
+
+    let (workspace_folder, workspace) = match &cargo.workspace {
+        Some(workspace) => (cargo_file_folder.to_path_buf(), workspace.clone()),
+        None => find_workspace_root(cargo_file_folder, cargo)
+            .ok_or_else(|| anyhow!("Failed to find the workspace root of {}", cargo_file_folder.display()))?,
+    };
+
+    let workspace_deps = workspace.dependencies.unwrap_or_default();
+
+    for deps in [&mut cargo.dependencies, &mut cargo.dev_dependencies, &mut cargo.build_dependencies].into_iter().flatten() {
+        for (name, dep) in deps.iter_mut().filter(|(_, dep)| is_inherited(dep)) {
+            let Dependency::Detailed(member_detail) = dep else {
+                unreachable!("Should not reach here");
+            };
+
+            let workspace_dep = workspace_deps
+                .get(name)
+                .ok_or_else(|| anyhow!("Dependency {} is inherited from the workspace but missing in [workspace.dependencies]", name))?;
+
+            *dep = inherit_workspace_dependency(member_detail, workspace_dep, workspace_folder.as_path());
+        }
+    }
+
+    Ok(())
+}
+
+fn inherit_workspace_dependency(member_detail: &DependencyDetail, workspace_dep: &Dependency, workspace_folder: &Path) -> Dependency {
+    let mut detail = match workspace_dep {
+        Dependency::Version(version) => DependencyDetailBuilder::default()
+            .version(version.clone())
+            .build()
+            .expect("Valid dependency detail"),
+        Dependency::Detailed(detail) => detail.clone(),
+    };
+
+    // The member can only add features to the ones of the workspace
+    if let Some(member_features) = &member_detail.features {
+        detail.features = Some(
+            detail.features
+                .unwrap_or_default()
+                .into_iter()
+                .chain(member_features.iter().cloned())
+                .unique()
+                .collect()
+        );
+    }
+
+    // Path in the workspace is relative to the workspace root
+    detail.path = detail.path.map(|path| {
+        workspace_folder.join(path).to_str().expect("Failed to convert path to string").to_string()
     });
 
+    Dependency::Detailed(detail)
+}
+
+// Workspace root the way cargo finds it, from `package.workspace` of the member
+// or the closest parent folder that its Cargo.toml has `[workspace]` that has the member
+pub(crate) fn find_workspace_root(member_folder: &Path, member: &CargoToml) -> Option<(PathBuf, Workspace)> {
+    // Relative path (even empty one for `Cargo.toml` in the current folder) does not have the parent folders to walk up
+    let member_folder = if member_folder.as_os_str().is_empty() { Path::new(".") } else { member_folder };
+    let member_folder = fs::canonicalize(member_folder).ok()?;
+    let member_folder = member_folder.as_path();
+
+    // Malformed manifest of a parent folder does not fail the member, the member is taken as outside of workspace
+    let read_workspace = |folder: &Path| match read_cargo_file(folder.join("Cargo.toml").as_path()) {
+        Ok(cargo) => cargo.workspace,
+        Err(err) => {
+            warn!("Skipped, Can't read the workspace root candidate: {:#}", err);
+            None
+        }
+    };
+
+    if let Some(workspace_path) = member.package.as_ref().and_then(|package| package.workspace.as_ref()) {
+        let workspace_folder = fs::canonicalize(member_folder.join(workspace_path)).ok()?;
+        let workspace = read_workspace(workspace_folder.as_path())?;
+
+        return Some((workspace_folder, workspace));
+    }
+
+    member_folder
+        .ancestors()
+        .skip(1)
+        .filter(|folder| folder.join("Cargo.toml").is_file())
+        .find_map(|folder| {
+            let workspace = read_workspace(folder)?;
+
+            is_workspace_member(folder, &workspace, member_folder).then(|| (folder.to_path_buf(), workspace))
+        })
+}
+
+// Like cargo, the members are the folders of `members` globs, and the path dependencies of the root package and the members
+// that are inside the workspace folder, both without the `exclude` paths
+fn is_workspace_member(workspace_folder: &Path, workspace: &Workspace, member_folder: &Path) -> bool {
+    let members = match get_workspace_members(workspace_folder, workspace) {
+        Ok(members) => members,
+        Err(err) => {
+            warn!("Skipped, Can't read the members of the workspace root candidate: {:#}", err);
+            return false;
+        }
+    };
+
+    let members = members.into_iter().filter_map(|folder| fs::canonicalize(folder).ok()).collect_vec();
+
+    if members.iter().any(|folder| folder == member_folder) {
+        return true;
+    }
+
+    let excluded = workspace.exclude
+        .iter()
+        .flatten()
+        .map(|path| workspace_folder.join(path))
+        .collect_vec();
+
+    let is_excluded = |folder: &Path| excluded.iter().any(|path| folder.starts_with(path));
+
+    if is_excluded(member_folder) {
+        return false;
+    }
+
+    let mut visited = HashSet::new();
+    let mut worklist = members;
+    worklist.push(workspace_folder.to_path_buf());
+
+    while let Some(folder) = worklist.pop() {
+        if !visited.insert(folder.clone()) {
+            continue;
+        }
+
+        let Ok(cargo) = read_cargo_file(folder.join("Cargo.toml").as_path()) else {
+            continue;
+        };
+
+        for dependency_folder in get_path_dependency_folders(&cargo, folder.as_path(), workspace_folder, workspace) {
+            let Ok(dependency_folder) = fs::canonicalize(dependency_folder) else {
+                continue;
+            };
+
+            if !dependency_folder.starts_with(workspace_folder) || is_excluded(dependency_folder.as_path()) {
+                continue;
+            }
+
+            if dependency_folder == member_folder {
+                return true;
+            }
+
+            worklist.push(dependency_folder);
+        }
+    }
+
+    false
+}
+
+// Folders of the path dependencies of all the dependencies tables, including the ones inherited from the workspace
+fn get_path_dependency_folders(cargo: &CargoToml, cargo_file_folder: &Path, workspace_folder: &Path, workspace: &Workspace) -> Vec<PathBuf> {
+    let target_deps = cargo.target
+        .iter()
+        .flat_map(|targets| targets.values())
+        .flat_map(|target_deps| [&target_deps.dependencies, &target_deps.dev_dependencies, &target_deps.build_dependencies]);
+
+    [&cargo.dependencies, &cargo.dev_dependencies, &cargo.build_dependencies]
+        .into_iter()
+        .chain(target_deps)
+        .flatten()
+        .flat_map(|deps| deps.iter())
+        .filter_map(|(name, dep)| match dep {
+            Dependency::Detailed(detail) if detail.workspace == Some(true) => {
+                match workspace.dependencies.as_ref()?.get(name)? {
+                    Dependency::Detailed(DependencyDetail { path: Some(path), .. }) => Some(workspace_folder.join(path)),
+                    _ => None,
+                }
+            }
+            Dependency::Detailed(DependencyDetail { path: Some(path), .. }) => Some(cargo_file_folder.join(path)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Folders of the workspace members, `members` and `default-members` globs are expanded without the `exclude` paths
+fn get_workspace_members(workspace_folder: &Path, workspace: &Workspace) -> Result<Vec<PathBuf>> {
+    let excluded = workspace.exclude
+        .iter()
+        .flatten()
+        .map(|path| workspace_folder.join(path))
+        .collect_vec();
+
+    let mut member_paths = vec![];
+
+    for member in workspace.members.iter().flatten().chain(workspace.default_members.iter().flatten()) {
+        let pattern = workspace_folder.join(member);
+        let pattern = pattern
+            .to_str()
+            .ok_or_else(|| anyhow!("Workspace member path {} is not valid UTF-8", pattern.display()))?;
 
-    return all_deps_specific;
+        let paths = glob::glob(pattern)
+            .with_context(|| format!("Invalid workspace member {} in {}", member, workspace_folder.join("Cargo.toml").display()))?;
+
+        member_paths.extend(paths.filter_map(|path| path.ok()));
+    }
+
+    Ok(member_paths
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .filter(|path| !excluded.iter().any(|excluded_path| path.starts_with(excluded_path)))
+        .filter(|path| {
+            let has_manifest = path.join("Cargo.toml").is_file();
+
+            if !has_manifest {
+                warn!("Workspace member {} does not have Cargo.toml, skipping", path.display());
+            }
+
+            has_manifest
+        })
+        .unique()
+        .collect())
 }
 
-// Return tuple of (merged dependencies with specific version and without local paths , local dependencies)
-fn get_deps_maps_from_cargo(cargo: CargoToml) -> (SpecificVersionDependencyMap, DependencyMap) {
-    let mut dependencies = cargo.dependencies.unwrap_or_default();
+// Return tuple of (merged dependencies with specific version and without local paths , local and git dependencies)
+pub(crate) fn get_deps_maps_from_cargo(cargo: CargoToml) -> (SpecificVersionDependencyMap, DependencyMap) {
+    let dependencies = cargo.dependencies.unwrap_or_default();
     let dev_dependencies = cargo.dev_dependencies.unwrap_or_default();
     let build_dependencies = cargo.build_dependencies.unwrap_or_default();
 
@@ -115,11 +762,11 @@ fn get_deps_maps_from_cargo(cargo: CargoToml) -> (SpecificVersionDependencyMap,
     all_deps.extend(dev_dependencies);
     all_deps.extend(build_dependencies);
 
-    let (remote_deps, local_deps): (Vec<(SpecificDependencyVersion, Dependency)>, Vec<(String, Dependency)>) = all_deps
+    all_deps
         .into_iter()
         .partition_map(|(k, v)| {
             let value = v.clone();
-            return match v {
+            match v {
                 Dependency::Version(version) => {
                     Left((
                         // Key
@@ -132,7 +779,7 @@ fn get_deps_maps_from_cargo(cargo: CargoToml) -> (SpecificVersionDependencyMap,
                     ))
                 }
                 Dependency::Detailed(details) => {
-                    if details.path.is_some() {
+                    if details.path.is_some() || details.git.is_some() {
                         return Right((
                             // Key
                             k.clone(),
@@ -140,24 +787,20 @@ fn get_deps_maps_from_cargo(cargo: CargoToml) -> (SpecificVersionDependencyMap,
                             value
                         ));
                     }
-                    let version = details.clone().version.expect("Must have version");
+                    // Like cargo, registry dependency without version takes any version
+                    let version = details.clone().version.unwrap_or("*".to_string());
                     Left((
                         // Key
                         SpecificDependencyVersion {
-                            name: k.clone(),
+                            name: details.package.clone().unwrap_or(k.clone()),
                             version,
                         },
                         // Value
                         value
                     ))
                 }
-            };
-        });
-
-    return (
-        remote_deps.iter().cloned().collect(),
-        local_deps.iter().cloned().collect()
-    );
+            }
+        })
 }
 
 #[cfg(test)]
@@ -167,6 +810,7 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::parse_cargo_files::test_utils::{lock_current_dir, with_current_dir};
 
     fn create_cargo_file(add: &str) -> String {
         // language=toml
@@ -178,14 +822,14 @@ edition = "2021"
 readme = "README.md"
         "#;
 
-        return format!("{}\n{}", base, add);
+        format!("{}\n{}", base, add)
     }
 
     fn create_dependency_map<const SIZE: usize>(deps_array: [(&str, Dependency); SIZE]) -> DependencyMap {
-        return deps_array.iter()
+        deps_array.iter()
             .cloned()
             .map(|(k, v)| (k.to_string(), v))
-            .collect();
+            .collect()
     }
 
     // #####################################################################
@@ -194,10 +838,11 @@ readme = "README.md"
 
     #[test]
     fn read_cargo_file() {
+        let _lock = lock_current_dir();
         let content = fs::read_to_string("Cargo.toml").expect("Failed to read Cargo.toml file");
 
         // Testing not crash
-        let result = parse_cargo_file(content);
+        let _result = parse_cargo_file(content).unwrap();
     }
 
     #[test]
@@ -227,7 +872,7 @@ build4 = { version = "0.14", default-features = true }
 build5 = { version = "0.15", default-features = true, features = ["else"] }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -342,7 +987,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
     fn no_deps() {
         let no_deps_cargo_toml = create_cargo_file("");
 
-        let cargo = parse_cargo_file(no_deps_cargo_toml);
+        let cargo = parse_cargo_file(no_deps_cargo_toml).unwrap();
 
         assert_eq!(cargo.dependencies.is_none(), true);
     }
@@ -352,7 +997,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         // language=toml
         let no_deps_cargo_toml = create_cargo_file("[dependencies]");
 
-        let cargo = parse_cargo_file(no_deps_cargo_toml);
+        let cargo = parse_cargo_file(no_deps_cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies map");
 
@@ -369,7 +1014,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         anyhow = "1.0"
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -389,7 +1034,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         reqwest = "0.11"
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -409,7 +1054,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         anyhow = { version = "1.0" }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -433,7 +1078,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         clap = { version = "4.1" }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -462,7 +1107,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         clap = { version = "4.1" }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -486,7 +1131,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         tokio = { version = "1.26", features = ["full"] }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -511,7 +1156,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         tokio = { version = "1.26", features = ["full"] }
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -541,7 +1186,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         tracing = {version = "0.1", default-features = false, features = ["std"]}
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -567,7 +1212,7 @@ build5 = { version = "0.15", default-features = true, features = ["else"] }
         tracing-subscriber = {version = "0.3", default-features = false, features = ["ansi", "env-filter", "fmt"]}
         "#.trim());
 
-        let cargo = parse_cargo_file(cargo_toml);
+        let cargo = parse_cargo_file(cargo_toml).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -617,7 +1262,7 @@ dep1 = "0.1"
 some_local = { path = "crates/some_local" }
         "#.trim();
 
-        let cargo = parse_cargo_file(root_cargo_toml.to_string());
+        let cargo = parse_cargo_file(root_cargo_toml.to_string()).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -651,7 +1296,7 @@ cryptography-x509 = { path = "../cryptography-x509" }
 
         "#.trim();
 
-        let cargo = parse_cargo_file(root_cargo_toml.to_string());
+        let cargo = parse_cargo_file(root_cargo_toml.to_string()).unwrap();
 
         let deps = cargo.dependencies.expect("Must have dependencies");
 
@@ -667,6 +1312,34 @@ cryptography-x509 = { path = "../cryptography-x509" }
         assert_eq!(deps, expected_deps);
     }
 
+    #[test]
+    fn renamed_dep_use_package_name() {
+        // language=toml
+        let cargo_toml = create_cargo_file(r#"
+        [dependencies]
+        my_serde = { version = "1.0", package = "serde" }
+        "#.trim());
+
+        let (deps, _) = get_deps_maps_from_cargo(parse_cargo_file(cargo_toml).unwrap());
+
+        let expected_deps = create_specific_version_dependency_map([
+            (
+                SpecificDependencyVersion {
+                    name: "serde".to_string(),
+                    version: "1.0".to_string(),
+                },
+                Dependency::Detailed(
+                    DependencyDetailBuilder::default()
+                        .version("1.0".to_string())
+                        .package("serde".to_string())
+                        .build().unwrap()
+                )
+            ),
+        ]);
+
+        assert_eq!(deps, expected_deps);
+    }
+
     // #####################################################################
     // Combined parsing file tests (support for local paths)
     // #####################################################################
@@ -686,22 +1359,24 @@ cryptography-x509 = { path = "../cryptography-x509" }
                 fs::create_dir_all(parent).expect("Failed to create dir")
             }
 
-            fs::write(file_path, content).expect(format!("Failed to write file \"{}\"", path).as_str());
+            fs::write(file_path, content).unwrap_or_else(|_| panic!("Failed to write file \"{}\"", path));
         });
 
-        return tmp_dir.to_str().expect("Failed to convert path to string").to_string();
+        tmp_dir.to_str().expect("Failed to convert path to string").to_string()
     }
 
     fn create_specific_version_dependency_map<const SIZE: usize>(deps_array: [(SpecificDependencyVersion, Dependency); SIZE]) -> SpecificVersionDependencyMap {
-        return deps_array.iter()
+        deps_array.iter()
             .cloned()
-            .collect();
+            .collect()
     }
 
     #[test]
     fn read_cargo_file_with_local() {
+        let _lock = lock_current_dir();
+
         // Testing not crash
-        parse_cargo_file_from_path("Cargo.toml".to_string());
+        parse_cargo_file_from_path("Cargo.toml".to_string(), &TargetFilter::default()).expect("Failed to parse Cargo.toml");
     }
 
     #[test]
@@ -767,7 +1442,7 @@ mydep = "1.0.0"
 
         let root_cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
 
-        let deps = parse_cargo_file_from_path(root_cargo_file_path);
+        let deps = parse_cargo_file_from_path(root_cargo_file_path, &TargetFilter::default()).unwrap().registry;
 
         let expected_deps = create_specific_version_dependency_map([
             (
@@ -814,7 +1489,697 @@ mydep = "1.0.0"
 
         assert_eq!(deps, expected_deps);
     }
-}
 
+    #[test]
+    fn parse_virtual_workspace_cargo_file() {
+        // language=toml
+        let root_cargo_toml = r#"
+[workspace]
+members = ["crates/*"]
+        "#.trim();
+
+        let cargo = parse_cargo_file(root_cargo_toml.to_string()).unwrap();
+
+        assert_eq!(cargo.package, None);
+        assert_eq!(cargo.workspace, Some(Workspace {
+            members: Some(vec!["crates/*".to_string()]),
+            ..Default::default()
+        }));
+    }
 
+    #[test]
+    fn support_virtual_workspace_members() {
+        // language=toml
+        let root_cargo_toml = r#"
+[workspace]
+members = ["crates/*"]
+exclude = ["crates/excluded"]
+default-members = ["tools/cli"]
+        "#.trim();
 
+        // language=toml
+        let l1_cargo_toml = r#"
+[package]
+name = "some_local_1"
+
+[dependencies]
+dep1 = "0.1"
+some_local_2 = { path = "../some_local_2" }
+        "#.trim();
+
+        // language=toml
+        let l2_cargo_toml = r#"
+[package]
+name = "some_local_2"
+
+[dev-dependencies]
+dep2 = "0.2"
+some_local_1 = { path = "../some_local_1" }
+        "#.trim();
+
+        // language=toml
+        let excluded_cargo_toml = r#"
+[package]
+name = "excluded"
+
+[dependencies]
+excluded-dep = "1.0"
+        "#.trim();
+
+        // language=toml
+        let cli_cargo_toml = r#"
+[package]
+name = "cli"
+
+[dependencies]
+dep3 = "0.3"
+        "#.trim();
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", root_cargo_toml),
+            ("crates/some_local_1/Cargo.toml", l1_cargo_toml),
+            ("crates/some_local_2/Cargo.toml", l2_cargo_toml),
+            ("crates/excluded/Cargo.toml", excluded_cargo_toml),
+            ("crates/README.md", "Not a member"),
+            ("tools/cli/Cargo.toml", cli_cargo_toml),
+        ]);
+
+        let root_cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let deps = parse_cargo_file_from_path(root_cargo_file_path, &TargetFilter::default()).unwrap().registry;
+
+        let expected_deps = create_specific_version_dependency_map([
+            (
+                SpecificDependencyVersion {
+                    name: "dep1".to_string(),
+                    version: "0.1".to_string(),
+                },
+                Dependency::Version("0.1".to_string())
+            ),
+            (
+                SpecificDependencyVersion {
+                    name: "dep2".to_string(),
+                    version: "0.2".to_string(),
+                },
+                Dependency::Version("0.2".to_string())
+            ),
+            (
+                SpecificDependencyVersion {
+                    name: "dep3".to_string(),
+                    version: "0.3".to_string(),
+                },
+                Dependency::Version("0.3".to_string())
+            ),
+        ]);
+
+        assert_eq!(deps, expected_deps);
+    }
+
+    #[test]
+    fn fail_for_invalid_workspace_member_glob() {
+        // language=toml
+        let root_cargo_toml = r#"
+[workspace]
+members = ["crates/[a"]
+        "#.trim();
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", root_cargo_toml),
+        ]);
+
+        let root_cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        assert_eq!(parse_cargo_file_from_path(root_cargo_file_path, &TargetFilter::default()).is_err(), true);
+    }
+
+    #[test]
+    fn support_workspace_dependencies() {
+        // language=toml
+        let root_cargo_toml = r#"
+[workspace]
+members = ["crates/*"]
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+anyhow = "1.0"
+some_local = { path = "crates/some_local" }
+        "#.trim();
+
+        // language=toml
+        let app_cargo_toml = r#"
+[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true, features = ["rc"] }
+anyhow.workspace = true
+some_local.workspace = true
+        "#.trim();
+
+        // language=toml
+        let local_cargo_toml = r#"
+[package]
+name = "some_local"
+
+[dependencies]
+dep1 = "0.1"
+        "#.trim();
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", root_cargo_toml),
+            ("crates/app/Cargo.toml", app_cargo_toml),
+            ("crates/some_local/Cargo.toml", local_cargo_toml),
+        ]);
+
+        let expected_deps = create_specific_version_dependency_map([
+            (
+                SpecificDependencyVersion {
+                    name: "serde".to_string(),
+                    version: "1.0".to_string(),
+                },
+                Dependency::Detailed(
+                    DependencyDetailBuilder::default()
+                        .version("1.0".to_string())
+                        .features(vec!["derive".to_string(), "rc".to_string()])
+                        .build().unwrap()
+                )
+            ),
+            (
+                SpecificDependencyVersion {
+                    name: "anyhow".to_string(),
+                    version: "1.0".to_string(),
+                },
+                Dependency::Detailed(
+                    DependencyDetailBuilder::default()
+                        .version("1.0".to_string())
+                        .build().unwrap()
+                )
+            ),
+            (
+                SpecificDependencyVersion {
+                    name: "dep1".to_string(),
+                    version: "0.1".to_string(),
+                },
+                Dependency::Version("0.1".to_string())
+            ),
+        ]);
+
+        // From the workspace root and from the member itself
+        for cargo_file in ["Cargo.toml", "crates/app/Cargo.toml"] {
+            let cargo_file_path = PathBuf::from(root_dir.as_str()).join(cargo_file).to_str().expect("Failed to convert path to string").to_string();
+
+            let deps = parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).unwrap().registry;
+
+            assert_eq!(deps, expected_deps);
+        }
+    }
+
+    #[test]
+    fn support_target_dependencies() {
+        // language=toml
+        let cargo_toml = create_cargo_file(r#"
+[dependencies]
+dep1 = "0.1"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[target.'cfg(windows)'.dependencies]
+windows-sys = "0.52"
+
+[target.x86_64-pc-windows-msvc.build-dependencies]
+winres = "0.1"
+        "#);
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", cargo_toml.as_str()),
+        ]);
+
+        let cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let dep_names = |targets: &TargetFilter| parse_cargo_file_from_path(cargo_file_path.clone(), targets)
+            .unwrap()
+            .registry
+            .into_keys()
+            .map(|key| key.name)
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(dep_names(&TargetFilter::default()), vec!["dep1", "libc", "windows-sys", "winres"]);
+        assert_eq!(
+            dep_names(&TargetFilter::with_cfgs("x86_64-unknown-linux-gnu", &["unix", "target_os=\"linux\""])),
+            vec!["dep1", "libc"]
+        );
+        assert_eq!(
+            dep_names(&TargetFilter::with_cfgs("x86_64-pc-windows-msvc", &["windows", "target_os=\"windows\""])),
+            vec!["dep1", "windows-sys", "winres"]
+        );
+    }
+
+    #[test]
+    fn support_git_dependencies() {
+        // language=toml
+        let cargo_toml = create_cargo_file(r#"
+[dependencies]
+dep1 = "0.1"
+my-git = { git = "https://github.com/foo/my-git", branch = "main" }
+renamed = { git = "https://github.com/foo/other", tag = "v1.0.0", package = "other" }
+        "#);
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", cargo_toml.as_str()),
+        ]);
+
+        let cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let deps = parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).unwrap();
+
+        assert_eq!(deps.registry.into_keys().map(|key| key.name).collect_vec(), vec!["dep1"]);
+        assert_eq!(deps.git, HashSet::from([
+            GitDependency {
+                name: "my-git".to_string(),
+                source: GitSource {
+                    url: "https://github.com/foo/my-git".to_string(),
+                    reference: Some(GitReference::Branch("main".to_string())),
+                    precise: None,
+                },
+                locked_version: None,
+            },
+            GitDependency {
+                name: "other".to_string(),
+                source: GitSource {
+                    url: "https://github.com/foo/other".to_string(),
+                    reference: Some(GitReference::Tag("v1.0.0".to_string())),
+                    precise: None,
+                },
+                locked_version: None,
+            },
+        ]));
+    }
+
+    #[test]
+    fn substitute_patched_dependencies() {
+        // language=toml
+        let cargo_toml = create_cargo_file(r#"
+[dependencies]
+dep1 = "0.1"
+dep2 = "0.2"
+dep3 = "1"
+dep1-fork = { git = "https://github.com/bar/dep1", package = "dep1" }
+dep4 = { version = "0.4", registry = "internal" }
+dep5 = "0.5"
+
+[patch.crates-io]
+dep1 = { path = "vendor/dep1" }
+dep2 = { git = "https://github.com/foo/dep2", branch = "fix" }
+
+[patch.internal]
+dep4 = { git = "https://github.com/foo/dep4" }
+dep5 = { git = "https://github.com/foo/dep5" }
+
+[replace]
+"dep3:1.0.0" = { path = "vendor/dep3" }
+        "#);
+
+        // language=toml
+        let dep1_cargo_toml = r#"
+[package]
+name = "dep1"
+version = "0.1.0"
+
+[dependencies]
+patched-only = "0.3"
+dep3 = "1"
+        "#;
+
+        // language=toml
+        let dep3_cargo_toml = r#"
+[package]
+name = "dep3"
+version = "1.0.0"
+
+[dependencies]
+dep3-dep = "2"
+        "#;
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", cargo_toml.as_str()),
+            ("vendor/dep1/Cargo.toml", dep1_cargo_toml),
+            ("vendor/dep3/Cargo.toml", dep3_cargo_toml),
+        ]);
+
+        let cargo_file_path = PathBuf::from(root_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let deps = parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).unwrap();
+
+        // The crates of the same name from other sources are kept, the replacements are collected even when
+        // no direct dependency uses them because they can replace transitive dependencies
+        assert_eq!(deps.registry.into_keys().map(|key| key.name).sorted().collect_vec(), vec!["dep3-dep", "dep5", "patched-only"]);
+        assert_eq!(
+            deps.git.into_iter().map(|git_dependency| (git_dependency.name, git_dependency.source.url)).sorted().collect_vec(),
+            vec![
+                ("dep1".to_string(), "https://github.com/bar/dep1".to_string()),
+                ("dep2".to_string(), "https://github.com/foo/dep2".to_string()),
+                ("dep4".to_string(), "https://github.com/foo/dep4".to_string()),
+                ("dep5".to_string(), "https://github.com/foo/dep5".to_string()),
+            ]
+        );
+        assert_eq!(deps.replaced.into_iter().sorted().collect_vec(), vec![
+            ("crates-io".to_string(), "dep1".to_string()),
+            ("crates-io".to_string(), "dep2".to_string()),
+            ("crates-io".to_string(), "dep3".to_string()),
+            ("internal".to_string(), "dep4".to_string()),
+            ("internal".to_string(), "dep5".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn take_patches_from_workspace_root() {
+        // language=toml
+        let workspace_cargo_toml = r#"
+[workspace]
+members = ["member"]
+
+[patch.crates-io]
+dep1 = { path = "vendor/dep1" }
+        "#;
+
+        // language=toml
+        let member_cargo_toml = r#"
+[package]
+name = "member"
+version = "0.1.0"
+
+[dependencies]
+dep1 = "0.1"
+        "#;
+
+        // language=toml
+        let dep1_cargo_toml = r#"
+[package]
+name = "dep1"
+version = "0.1.0"
+
+[dependencies]
+patched-only = "0.3"
+        "#;
+
+        let root_dir = save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", workspace_cargo_toml),
+            ("member/Cargo.toml", member_cargo_toml),
+            ("vendor/dep1/Cargo.toml", dep1_cargo_toml),
+        ]);
+
+        let cargo_file_path = PathBuf::from(root_dir).join("member").join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let deps = parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).unwrap();
+
+        assert_eq!(deps.registry.into_keys().map(|key| key.name).collect_vec(), vec!["patched-only"]);
+
+        // Other input without the patch keeps the upstream crate
+        let other_dir = save_map_as_files_in_tmp_dir([("Cargo.toml", member_cargo_toml)]);
+        let other_cargo_file_path = PathBuf::from(other_dir).join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        let other_deps = parse_cargo_file_from_path(other_cargo_file_path, &TargetFilter::default()).unwrap();
+
+        assert_eq!(other_deps.registry.into_keys().map(|key| key.name).collect_vec(), vec!["dep1"]);
+    }
+
+    #[test]
+    fn parse_source_and_name_of_package_id_spec() {
+        assert_eq!(parse_package_id_spec("foo:1.0.0"), ("crates-io".to_string(), "foo".to_string()));
+        assert_eq!(parse_package_id_spec("foo@1.0.0"), ("crates-io".to_string(), "foo".to_string()));
+        assert_eq!(parse_package_id_spec("https://github.com/rust-lang/crates.io-index#foo@1.0.0"), ("crates-io".to_string(), "foo".to_string()));
+        assert_eq!(parse_package_id_spec("git+https://github.com/foo/bar.git#0.1.0"), ("https://github.com/foo/bar".to_string(), "bar".to_string()));
+    }
+
+    #[test]
+    fn parse_chosen_workspace_members() {
+        // language=toml
+        let workspace_cargo_toml = r#"
+[workspace]
+members = ["cli", "server", "common"]
+
+[workspace.dependencies]
+serde = "1"
+        "#;
+
+        // language=toml
+        let cli_cargo_toml = r#"
+[package]
+name = "cli"
+version = "0.1.0"
+
+[dependencies]
+clap = "4"
+common = { path = "../common" }
+        "#;
+
+        // language=toml
+        let server_cargo_toml = r#"
+[package]
+name = "server"
+version = "0.1.0"
+
+[dependencies]
+tokio = "1"
+        "#;
+
+        // language=toml
+        let common_cargo_toml = r#"
+[package]
+name = "common"
+version = "0.1.0"
+
+[dependencies]
+serde = { workspace = true }
+        "#;
+
+        let root_dir = PathBuf::from(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", workspace_cargo_toml),
+            ("cli/Cargo.toml", cli_cargo_toml),
+            ("server/Cargo.toml", server_cargo_toml),
+            ("common/Cargo.toml", common_cargo_toml),
+        ]));
+
+        let dep_names = |cargo_file_path: PathBuf, members: &[&str]| {
+            let members = members.iter().map(|member| member.to_string()).collect_vec();
+
+            parse_workspace_members_from_path(cargo_file_path.as_path(), &TargetFilter::default(), &members)
+                .map(|deps| deps.registry.into_keys().map(|key| key.name).sorted().collect_vec())
+        };
+
+        assert_eq!(dep_names(root_dir.join("Cargo.toml"), &["cli"]).unwrap(), vec!["clap", "serde"]);
+        assert_eq!(dep_names(root_dir.join("Cargo.toml"), &["cli", "server"]).unwrap(), vec!["clap", "serde", "tokio"]);
+        // The workspace is found from any of its members
+        assert_eq!(dep_names(root_dir.join("server").join("Cargo.toml"), &["common"]).unwrap(), vec!["serde"]);
+        assert_eq!(dep_names(root_dir.join("Cargo.toml"), &["missing"]).is_err(), true);
+
+        // Like running with `--cargo-file Cargo.toml -p <member>` in the workspace root or in a member folder
+        let relative_dep_names = |folder: PathBuf, members: &[&str]| with_current_dir(folder.as_path(), || dep_names(PathBuf::from("Cargo.toml"), members));
+
+        assert_eq!(relative_dep_names(root_dir.clone(), &["cli"]).unwrap(), vec!["clap", "serde"]);
+        assert_eq!(relative_dep_names(root_dir.join("server"), &["common"]).unwrap(), vec!["serde"]);
+    }
+
+    #[test]
+    fn find_workspace_root_like_cargo() {
+        // language=toml
+        let workspace_cargo_toml = r#"
+[workspace]
+members = ["crates/*"]
+exclude = ["crates/standalone"]
+        "#;
+
+        // language=toml
+        let outer_workspace_cargo_toml = r#"
+[workspace]
+members = ["repo/crates/standalone"]
+        "#;
+
+        // language=toml
+        let explicit_member_cargo_toml = r#"
+[package]
+name = "explicit"
+version = "0.1.0"
+workspace = "../repo"
+        "#;
+
+        // language=toml
+        let member_cargo_toml = r#"
+[package]
+name = "member"
+version = "0.1.0"
+        "#;
+
+        // The workspace root is found from the canonical path of the member
+        let root_dir = fs::canonicalize(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", outer_workspace_cargo_toml),
+            ("repo/Cargo.toml", workspace_cargo_toml),
+            ("repo/crates/member/Cargo.toml", member_cargo_toml),
+            ("repo/crates/standalone/Cargo.toml", member_cargo_toml),
+            ("explicit/Cargo.toml", explicit_member_cargo_toml),
+        ])).unwrap();
+
+        let workspace_folder = |member: &str| {
+            let member_folder = root_dir.join(member);
+            let cargo = super::read_cargo_file(member_folder.join("Cargo.toml").as_path()).unwrap();
+
+            find_workspace_root(member_folder.as_path(), &cargo).map(|(folder, _)| folder)
+        };
+
+        assert_eq!(workspace_folder("repo/crates/member"), Some(root_dir.join("repo")));
+        // Excluded member belongs to the next parent workspace
+        assert_eq!(workspace_folder("repo/crates/standalone"), Some(root_dir.clone()));
+        assert_eq!(workspace_folder("explicit"), Some(root_dir.join("repo")));
+    }
+
+    #[test]
+    fn find_workspace_root_only_for_members() {
+        // language=toml
+        let workspace_cargo_toml = r#"
+[workspace]
+members = ["crates/cli"]
+
+[workspace.dependencies]
+shared = { path = "crates/shared" }
+        "#;
+
+        // language=toml
+        let cli_cargo_toml = r#"
+[package]
+name = "cli"
+version = "0.1.0"
+
+[dependencies]
+helper = { path = "../helper" }
+shared = { workspace = true }
+        "#;
+
+        // language=toml
+        let package_cargo_toml = r#"
+[package]
+name = "package"
+version = "0.1.0"
+        "#;
+
+        let root_dir = fs::canonicalize(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", workspace_cargo_toml),
+            ("crates/cli/Cargo.toml", cli_cargo_toml),
+            ("crates/helper/Cargo.toml", package_cargo_toml),
+            ("crates/shared/Cargo.toml", package_cargo_toml),
+            ("crates/unrelated/Cargo.toml", package_cargo_toml),
+        ])).unwrap();
+
+        let workspace_folder = |member: &str| {
+            let member_folder = root_dir.join(member);
+            let cargo = super::read_cargo_file(member_folder.join("Cargo.toml").as_path()).unwrap();
+
+            find_workspace_root(member_folder.as_path(), &cargo).map(|(folder, _)| folder)
+        };
+
+        assert_eq!(workspace_folder("crates/cli"), Some(root_dir.clone()));
+        // Path dependencies of the members are members as well
+        assert_eq!(workspace_folder("crates/helper"), Some(root_dir.clone()));
+        assert_eq!(workspace_folder("crates/shared"), Some(root_dir.clone()));
+        // Package that is neither in `members` nor a path dependency is outside of the workspace
+        assert_eq!(workspace_folder("crates/unrelated"), None);
+    }
+
+    #[test]
+    fn inherit_workspace_dependencies_of_relative_member_path() {
+        // language=toml
+        let workspace_cargo_toml = r#"
+[workspace]
+members = ["crates/*"]
+
+[workspace.dependencies]
+serde = "1.0"
+        "#;
+
+        // language=toml
+        let member_cargo_toml = r#"
+[package]
+name = "member"
+version = "0.1.0"
+
+[dependencies]
+serde = { workspace = true }
+        "#;
+
+        let root_dir = PathBuf::from(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", workspace_cargo_toml),
+            ("crates/member/Cargo.toml", member_cargo_toml),
+        ]));
+
+        // Like running in the member folder with `--cargo-file Cargo.toml`
+        let deps = with_current_dir(root_dir.join("crates/member").as_path(), || {
+            parse_cargo_file_from_path("Cargo.toml".to_string(), &TargetFilter::default())
+        }).expect("Failed to parse Cargo.toml");
+
+        assert_eq!(deps.registry.into_keys().map(|key| key.name).collect_vec(), vec!["serde"]);
+    }
+
+    #[test]
+    fn fail_for_malformed_cargo_file() {
+        // language=toml
+        let malformed_cargo_toml = r#"
+[package]
+name = "malformed"
+version = { not-a = "version" }
+        "#;
+
+        let root_dir = PathBuf::from(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", malformed_cargo_toml),
+        ]));
+
+        let cargo_file_path = root_dir.join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+        let err = parse_cargo_file_from_path(cargo_file_path.clone(), &TargetFilter::default()).expect_err("Malformed Cargo.toml");
+
+        assert_eq!(err.to_string(), format!("Failed to parse Cargo.toml file at {}", cargo_file_path));
+    }
+
+    #[test]
+    fn skip_malformed_parent_manifest_when_finding_workspace_root() {
+        // language=toml
+        let member_cargo_toml = r#"
+[package]
+name = "member"
+version = "0.1.0"
+
+[dependencies]
+serde = "1"
+        "#;
+
+        let root_dir = PathBuf::from(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", "[workspace"),
+            ("member/Cargo.toml", member_cargo_toml),
+        ]));
+
+        let cargo_file_path = root_dir.join("member/Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+        let deps = parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).expect("Failed to parse Cargo.toml");
+
+        assert_eq!(deps.registry.into_keys().map(|key| key.name).collect_vec(), vec!["serde"]);
+    }
+
+    #[test]
+    fn fail_for_inherited_dependency_without_workspace() {
+        // language=toml
+        let member_cargo_toml = r#"
+[package]
+name = "member"
+version = "0.1.0"
+
+[dependencies]
+serde = { workspace = true }
+        "#;
+
+        let root_dir = PathBuf::from(save_map_as_files_in_tmp_dir([
+            ("Cargo.toml", member_cargo_toml),
+        ]));
+
+        let cargo_file_path = root_dir.join("Cargo.toml").to_str().expect("Failed to convert path to string").to_string();
+
+        assert_eq!(parse_cargo_file_from_path(cargo_file_path, &TargetFilter::default()).is_err(), true);
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+
+use crate::parse_cargo_files::parse_lock_file::Package;
+
+// Packages of Cargo.lock with the exact packages that each one depends on,
+// so questions about the dependencies are answered without the index
+#[derive(Debug)]
+pub struct LockFileGraph {
+    packages: Vec<Package>,
+    // Indexes of the packages that each package depends on
+    edges: Vec<Vec<usize>>,
+}
+
+impl LockFileGraph {
+    pub fn new(packages: Vec<Package>) -> Result<Self> {
+        let packages_by_name = packages
+            .iter()
+            .enumerate()
+            .into_group_map_by(|(_, package)| package.name.clone());
+
+        let edges = packages
+            .iter()
+            .map(|package| {
+                package
+                    .parsed_dependencies()?
+                    .into_iter()
+                    .map(|dependency| {
+                        let matched = packages_by_name
+                            .get(&dependency.name)
+                            .into_iter()
+                            .flatten()
+                            .filter(|(_, candidate)| dependency.matches(candidate))
+                            .map(|(index, _)| *index)
+                            .collect_vec();
+
+                        match matched.as_slice() {
+                            [index] => Ok(*index),
+                            [] => Err(anyhow!("Dependency {} of {} {} is missing from Cargo.lock", dependency.name, package.name, package.version)),
+                            _ => Err(anyhow!("Dependency {} of {} {} matches several packages in Cargo.lock", dependency.name, package.name, package.version)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(LockFileGraph { packages, edges })
+    }
+
+    pub fn packages(&self) -> &[Package] {
+        &self.packages
+    }
+
+    // Indexes of the exact packages that the package depends on
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        &self.edges[index]
+    }
+
+    // Workspace members and their path dependencies do not have source
+    fn local_packages(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.packages.len()).filter(|index| self.packages[*index].source.is_none())
+    }
+
+    // The chosen local packages and every package they depend on, like `cargo build -p <member>`
+    pub fn members_closure(&self, member_names: &[String]) -> Result<Vec<Package>> {
+        let roots = member_names
+            .iter()
+            .map(|member_name| {
+                self.local_packages()
+                    .find(|index| self.packages[*index].name == *member_name)
+                    .ok_or_else(|| anyhow!(
+                        "Package {} is not a member in Cargo.lock, the local packages are: {}",
+                        member_name,
+                        self.local_packages().map(|index| self.packages[index].name.as_str()).sorted().join(", ")
+                    ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut visited = HashSet::new();
+        let mut worklist = roots;
+
+        while let Some(index) = worklist.pop() {
+            if visited.insert(index) {
+                worklist.extend(self.dependencies(index));
+            }
+        }
+
+        Ok(visited.into_iter().sorted().map(|index| self.packages[index].clone()).collect())
+    }
+
+    // The shortest chain of dependencies from each top level local package to the package, for `why`
+    pub fn why(&self, target: usize) -> Vec<Vec<&Package>> {
+        let depended_on: HashSet<usize> = self.edges.iter().flatten().copied().collect();
+
+        self.local_packages()
+            .filter(|index| !depended_on.contains(index))
+            .filter_map(|root| self.shortest_path(root, target))
+            .map(|path| path.into_iter().map(|index| &self.packages[index]).collect())
+            .collect()
+    }
+
+    fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut previous = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                let mut path = vec![to];
+                let mut current = to;
+
+                while current != from {
+                    current = previous[&current];
+                    path.push(current);
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            for &dependency in self.dependencies(index) {
+                if let Entry::Vacant(entry) = previous.entry(dependency) {
+                    entry.insert(index);
+                    queue.push_back(dependency);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::parse_cargo_files::parse_lock_file::parse_cargo_lock_file;
+
+    use super::*;
+
+    // language=toml
+    const CARGO_LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "common",
+ "serde_json",
+]
+
+[[package]]
+name = "common"
+version = "0.1.0"
+dependencies = [
+ "serde 1.0.193",
+]
+
+[[package]]
+name = "tool"
+version = "0.1.0"
+dependencies = [
+ "serde 0.9.15 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "serde"
+version = "0.9.15"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.193"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde_json"
+version = "1.0.108"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "serde 1.0.193",
+]
+    "#;
+
+    fn graph() -> LockFileGraph {
+        let cargo_lock = parse_cargo_lock_file(CARGO_LOCK.to_string()).expect("Valid Cargo.lock");
+
+        LockFileGraph::new(cargo_lock.package.unwrap()).expect("Valid graph")
+    }
+
+    fn names_and_versions<'a>(packages: impl IntoIterator<Item = &'a Package>) -> Vec<String> {
+        packages.into_iter().map(|package| format!("{} {}", package.name, package.version)).collect()
+    }
+
+    #[test]
+    fn resolve_dependency_edges() {
+        let graph = graph();
+        let dependencies = |index: usize| names_and_versions(graph.dependencies(index).iter().map(|dependency| &graph.packages()[*dependency]));
+
+        assert_eq!(dependencies(0), vec!["common 0.1.0", "serde_json 1.0.108"]);
+        assert_eq!(dependencies(2), vec!["serde 0.9.15"]);
+        assert_eq!(dependencies(5), vec!["serde 1.0.193"]);
+    }
+
+    #[test]
+    fn collect_members_closure() {
+        let graph = graph();
+
+        assert_eq!(
+            names_and_versions(&graph.members_closure(&["app".to_string()]).unwrap()),
+            vec!["app 0.1.0", "common 0.1.0", "serde 1.0.193", "serde_json 1.0.108"]
+        );
+        assert_eq!(names_and_versions(&graph.members_closure(&["tool".to_string()]).unwrap()), vec!["tool 0.1.0", "serde 0.9.15"]);
+        assert_eq!(graph.members_closure(&["serde".to_string()]).is_err(), true);
+    }
+
+    #[test]
+    fn explain_why_package_is_needed() {
+        let graph = graph();
+
+        let paths = graph.why(4).into_iter().map(names_and_versions).collect_vec();
+
+        assert_eq!(paths, vec![vec!["app 0.1.0", "common 0.1.0", "serde 1.0.193"]]);
+    }
+
+    #[test]
+    fn fail_for_missing_dependency() {
+        let cargo_lock = parse_cargo_lock_file(r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "missing",
+]
+        "#.to_string()).expect("Valid Cargo.lock");
+
+        assert_eq!(LockFileGraph::new(cargo_lock.package.unwrap()).is_err(), true);
+    }
+}
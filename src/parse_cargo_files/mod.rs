@@ -1,2 +1,28 @@
+pub mod cargo_metadata;
 pub mod cargo_toml_file;
+pub mod lock_file_graph;
+pub mod package_source;
 pub mod parse_lock_file;
+#[cfg(test)]
+pub mod test_utils {
+    use std::path::Path;
+    use std::sync::{Mutex, MutexGuard};
+
+    // The current folder is shared by all the tests, tests that read relative paths hold the lock
+    static CURRENT_DIR: Mutex<()> = Mutex::new(());
+
+    pub fn lock_current_dir() -> MutexGuard<'static, ()> {
+        CURRENT_DIR.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn with_current_dir<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+        let _lock = lock_current_dir();
+        let previous_dir = std::env::current_dir().expect("Failed to get current dir");
+
+        std::env::set_current_dir(dir).expect("Failed to change current dir");
+        let result = f();
+        std::env::set_current_dir(previous_dir).expect("Failed to restore current dir");
+
+        result
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+
+pub const CRATES_IO_INDEX_URL: &str = "https://github.com/rust-lang/crates.io-index";
+pub const CRATES_IO_SPARSE_INDEX_URL: &str = "sparse+https://index.crates.io/";
+
+// The `source` of a package in Cargo.lock
+// (see https://doc.rust-lang.org/cargo/reference/registry-index.html and `SourceId` in cargo)
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum PackageSource {
+    // Index url of the registry, sparse registries keep the `sparse+` prefix
+    Registry(String),
+    Git(GitSource),
+    Path(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct GitSource {
+    pub url: String,
+    pub reference: Option<GitReference>,
+    // The exact commit that was locked
+    pub precise: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum GitReference {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
+
+pub fn is_crates_io_index_url(index_url: &str) -> bool {
+    let index_url = index_url.trim_end_matches('/');
+
+    index_url == CRATES_IO_INDEX_URL || index_url == CRATES_IO_SPARSE_INDEX_URL.trim_end_matches('/')
+}
+
+impl FromStr for PackageSource {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let (kind, url) = source
+            .split_once('+')
+            .ok_or_else(|| anyhow!("Invalid package source {}, missing source kind", source))?;
+
+        match kind {
+            "registry" => Ok(PackageSource::Registry(url.to_string())),
+            "sparse" => Ok(PackageSource::Registry(source.to_string())),
+            "path" => Ok(PackageSource::Path(url.to_string())),
+            "git" => Ok(PackageSource::Git(parse_git_source(url))),
+            _ => Err(anyhow!("Unsupported package source kind {} in {}", kind, source)),
+        }
+    }
+}
+
+fn parse_git_source(source: &str) -> GitSource {
+    let (url, precise) = match source.split_once('#') {
+        Some((url, precise)) => (url, Some(precise.to_string())),
+        None => (source, None),
+    };
+
+    let (url, query) = match url.split_once('?') {
+        Some((url, query)) => (url, Some(query)),
+        None => (url, None),
+    };
+
+    let reference = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(key, value)| match key {
+            "branch" => Some(GitReference::Branch(value.to_string())),
+            "tag" => Some(GitReference::Tag(value.to_string())),
+            "rev" => Some(GitReference::Rev(value.to_string())),
+            _ => None,
+        });
+
+    GitSource {
+        url: url.to_string(),
+        reference,
+        precise,
+    }
+}
+
+impl Display for PackageSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageSource::Registry(index_url) if index_url.starts_with("sparse+") => write!(f, "{}", index_url),
+            PackageSource::Registry(index_url) => write!(f, "registry+{}", index_url),
+            PackageSource::Path(path) => write!(f, "path+{}", path),
+            PackageSource::Git(git) => write!(f, "git+{}", git),
+        }
+    }
+}
+
+impl Display for GitSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)?;
+
+        match &self.reference {
+            Some(GitReference::Branch(branch)) => write!(f, "?branch={}", branch)?,
+            Some(GitReference::Tag(tag)) => write!(f, "?tag={}", tag)?,
+            Some(GitReference::Rev(rev)) => write!(f, "?rev={}", rev)?,
+            None => {}
+        }
+
+        if let Some(precise) = &self.precise {
+            write!(f, "#{}", precise)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_crates_io_source() {
+        let source: PackageSource = "registry+https://github.com/rust-lang/crates.io-index".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Registry(CRATES_IO_INDEX_URL.to_string()));
+        assert_eq!(is_crates_io_index_url(CRATES_IO_INDEX_URL), true);
+    }
+
+    #[test]
+    fn parse_crates_io_sparse_source() {
+        let source: PackageSource = "sparse+https://index.crates.io/".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Registry(CRATES_IO_SPARSE_INDEX_URL.to_string()));
+        assert_eq!(is_crates_io_index_url(CRATES_IO_SPARSE_INDEX_URL), true);
+    }
+
+    #[test]
+    fn parse_other_registry_source() {
+        let source: PackageSource = "registry+https://our.registry/index".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Registry("https://our.registry/index".to_string()));
+        assert_eq!(is_crates_io_index_url("https://our.registry/index"), false);
+    }
+
+    #[test]
+    fn parse_sparse_registry_source() {
+        let source: PackageSource = "sparse+https://our.registry/index/".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Registry("sparse+https://our.registry/index/".to_string()));
+    }
+
+    #[test]
+    fn parse_git_source() {
+        let source: PackageSource = "git+https://github.com/foo/bar?branch=main#0123456789abcdef".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Git(GitSource {
+            url: "https://github.com/foo/bar".to_string(),
+            reference: Some(GitReference::Branch("main".to_string())),
+            precise: Some("0123456789abcdef".to_string()),
+        }));
+        assert_eq!(source.to_string(), "git+https://github.com/foo/bar?branch=main#0123456789abcdef");
+    }
+
+    #[test]
+    fn parse_git_source_without_reference() {
+        let source: PackageSource = "git+https://github.com/foo/bar#0123456789abcdef".parse().expect("Valid source");
+
+        assert_eq!(source, PackageSource::Git(GitSource {
+            url: "https://github.com/foo/bar".to_string(),
+            reference: None,
+            precise: Some("0123456789abcdef".to_string()),
+        }));
+    }
+
+    #[test]
+    fn parse_invalid_source() {
+        assert_eq!("https://github.com/foo/bar".parse::<PackageSource>().is_err(), true);
+        assert_eq!("unknown+https://github.com/foo/bar".parse::<PackageSource>().is_err(), true);
+    }
+}
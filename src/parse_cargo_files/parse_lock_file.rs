@@ -1,17 +1,26 @@
-use std::fs;
-use std::hash::Hash;
 
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
 use derive_builder::Builder;
-use itertools::Itertools;
 use serde::Deserialize;
+use tracing::warn;
+
+// The newest Cargo.lock version that is known to parse
+const LATEST_KNOWN_LOCK_FILE_VERSION: u32 = 4;
 
 #[derive(Debug, Deserialize, PartialEq, Clone, Builder)]
 pub struct CargoLockToml {
+    // Missing in version 1 and 2 lock files
     #[allow(dead_code)] // Disable dead code warning for the entire struct
-    #[builder(default = "3")]
-    pub version: u8,
+    #[builder(setter(into, strip_option), default = "Some(3)")]
+    pub version: Option<u32>,
     #[allow(dead_code)] // Disable dead code warning for the entire struct
     pub package: Option<Vec<Package>>,
+    // Version 1 lock files keep the checksums here with keys like `checksum <name> <version> (<source>)`
+    #[builder(setter(into, strip_option), default)]
+    pub metadata: Option<HashMap<String, String>>,
 }
 
 
@@ -34,14 +43,87 @@ pub struct Package {
     pub dependencies: Option<Vec<String>>,
 }
 
-pub fn parse_cargo_lock_file(content: String) -> CargoLockToml {
-    let cargo_toml: CargoLockToml = toml::from_str(&content).expect("Failed to deserialize Cargo.lock");
+// Entry of `dependencies` of package, `name`, `name version` or `name version (source)`,
+// the version and source are written only when they are needed to tell apart packages with the same name
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LockFileDependency {
+    pub name: String,
+    pub version: Option<String>,
+    pub source: Option<String>,
+}
+
+impl FromStr for LockFileDependency {
+    type Err = anyhow::Error;
+
+    fn from_str(dependency: &str) -> Result<Self> {
+        let mut parts = dependency.splitn(3, ' ');
+
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| anyhow!("Empty dependency in Cargo.lock"))?;
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|source| {
+                source
+                    .strip_prefix('(')
+                    .and_then(|source| source.strip_suffix(')'))
+                    .ok_or_else(|| anyhow!("Invalid source of dependency {} in Cargo.lock", dependency))
+            })
+            .transpose()?;
+
+        Ok(LockFileDependency {
+            name: name.to_string(),
+            version: version.map(|version| version.to_string()),
+            source: source.map(|source| source.to_string()),
+        })
+    }
+}
 
-    if cargo_toml.version != 3 {
-        panic!("Cargo.lock version must be 3");
+impl LockFileDependency {
+    pub fn matches(&self, package: &Package) -> bool {
+        self.name == package.name
+            && self.version.as_ref().is_none_or(|version| *version == package.version)
+            && self.source.as_ref().is_none_or(|source| Some(source) == package.source.as_ref())
     }
+}
 
-    return cargo_toml;
+impl Package {
+    pub fn parsed_dependencies(&self) -> Result<Vec<LockFileDependency>> {
+        self.dependencies
+            .iter()
+            .flatten()
+            .map(|dependency| dependency.parse())
+            .collect()
+    }
+}
+
+pub fn parse_cargo_lock_file(content: String) -> Result<CargoLockToml> {
+    let mut cargo_lock: CargoLockToml = toml::from_str(&content).context("Failed to deserialize Cargo.lock")?;
+
+    match cargo_lock.version {
+        None | Some(1..=LATEST_KNOWN_LOCK_FILE_VERSION) => {}
+        Some(0) => return Err(anyhow!("Unknown Cargo.lock version 0")),
+        Some(version) => warn!("Cargo.lock version {} is newer than the supported versions, trying to read it anyway", version),
+    }
+
+    // Move the checksums of version 1 lock file to their packages
+    if let Some(metadata) = cargo_lock.metadata.as_ref() {
+        for package in cargo_lock.package.iter_mut().flatten().filter(|package| package.checksum.is_none()) {
+            let Some(source) = package.source.as_ref() else {
+                continue;
+            };
+
+            package.checksum = metadata
+                .get(&format!("checksum {} {} ({})", package.name, package.version, source))
+                // Packages without checksum have `<none>`
+                .filter(|checksum| checksum.as_str() != "<none>")
+                .cloned();
+        }
+    }
+
+    Ok(cargo_lock)
 }
 
 #[cfg(test)]
@@ -51,6 +133,7 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::parse_cargo_files::test_utils::lock_current_dir;
 
     fn create_cargo_lock_file(add: &str) -> String {
         // language=toml
@@ -60,7 +143,7 @@ mod tests {
 version = 3
         "#;
 
-        return format!("{}\n{}", base, add);
+        format!("{}\n{}", base, add)
     }
 
 
@@ -70,17 +153,18 @@ version = 3
 
     #[test]
     fn read_cargo_lock_file() {
+        let _lock = lock_current_dir();
         let content = fs::read_to_string("Cargo.lock").expect("Failed to read Cargo.lock file");
 
         // Testing not crash
-        let result = parse_cargo_lock_file(content);
+        let _result = parse_cargo_lock_file(content).expect("Valid Cargo.lock");
     }
 
     #[test]
     fn no_packages() {
         let cargo_lock = create_cargo_lock_file("");
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         assert_eq!(cargo.package, None);
     }
@@ -96,7 +180,7 @@ source = "registry+https://github.com/rust-lang/crates.io-index"
 checksum = "7de8ce5e0f9f8d88245311066a578d72b7af3e7088f32783804676302df237e4"
         "#);
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         let expected_packages = vec![
             PackageBuilder::default()
@@ -129,7 +213,7 @@ checksum = "d468802bab17cbc0cc575e9b053f41e72aa36bfa6b7f55e3529ffa43161b97fa"
 
         "#);
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         let expected_packages = vec![
             PackageBuilder::default()
@@ -172,7 +256,7 @@ checksum = "2dffe52ecf27772e601905b7522cb4ef790d2cc203488bbd0e2fe85fcb74566d"
 
         "#);
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         let expected_packages = vec![
             PackageBuilder::default()
@@ -203,7 +287,7 @@ name = "cryptography-x509"
 version = "0.1.0"
         "#);
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         let expected_packages = vec![
             PackageBuilder::default()
@@ -230,7 +314,7 @@ name = "cryptography-x509"
 version = "0.1.0"
         "#);
 
-        let cargo = parse_cargo_lock_file(cargo_lock);
+        let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
 
         let expected_packages = vec![
 
@@ -249,7 +333,98 @@ version = "0.1.0"
 
         assert_eq!(cargo.package.unwrap(), expected_packages);
     }
-}
 
+    #[test]
+    fn parse_dependency_forms() {
+        assert_eq!("memchr".parse::<LockFileDependency>().unwrap(), LockFileDependency {
+            name: "memchr".to_string(),
+            version: None,
+            source: None,
+        });
+        assert_eq!("memchr 2.5.0".parse::<LockFileDependency>().unwrap(), LockFileDependency {
+            name: "memchr".to_string(),
+            version: Some("2.5.0".to_string()),
+            source: None,
+        });
+        assert_eq!("memchr 2.5.0 (registry+https://github.com/rust-lang/crates.io-index)".parse::<LockFileDependency>().unwrap(), LockFileDependency {
+            name: "memchr".to_string(),
+            version: Some("2.5.0".to_string()),
+            source: Some("registry+https://github.com/rust-lang/crates.io-index".to_string()),
+        });
+        assert_eq!("memchr 2.5.0 registry+https://github.com/rust-lang/crates.io-index".parse::<LockFileDependency>().is_err(), true);
+    }
 
+    #[test]
+    fn version_1_with_metadata_checksums() {
+        // language=toml
+        let cargo_lock = r#"
+[[package]]
+name = "aho-corasick"
+version = "0.7.20"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "memchr 2.5.0 (registry+https://github.com/rust-lang/crates.io-index)",
+]
 
+[[package]]
+name = "memchr"
+version = "2.5.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "my-crate"
+version = "0.1.0"
+
+[metadata]
+"checksum aho-corasick 0.7.20 (registry+https://github.com/rust-lang/crates.io-index)" = "cc936419f96fa211c1b9166887b38e5e40b19958e5b895be7c1f93adec7071ac"
+"checksum memchr 2.5.0 (registry+https://github.com/rust-lang/crates.io-index)" = "<none>"
+        "#;
+
+        let cargo = parse_cargo_lock_file(cargo_lock.to_string()).expect("Valid Cargo.lock");
+
+        assert_eq!(cargo.version, None);
+        assert_eq!(
+            cargo.package.unwrap().iter().map(|package| (package.name.as_str(), package.checksum.as_deref())).collect::<Vec<_>>(),
+            vec![
+                ("aho-corasick", Some("cc936419f96fa211c1b9166887b38e5e40b19958e5b895be7c1f93adec7071ac")),
+                ("memchr", None),
+                ("my-crate", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn version_2_without_version_key() {
+        // language=toml
+        let cargo_lock = r#"
+[[package]]
+name = "memchr"
+version = "2.5.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "2dffe52ecf27772e601905b7522cb4ef790d2cc203488bbd0e2fe85fcb74566d"
+        "#;
+
+        let cargo = parse_cargo_lock_file(cargo_lock.to_string()).expect("Valid Cargo.lock");
+
+        assert_eq!(cargo.version, None);
+        assert_eq!(cargo.package.unwrap()[0].checksum, Some("2dffe52ecf27772e601905b7522cb4ef790d2cc203488bbd0e2fe85fcb74566d".to_string()));
+    }
+
+    #[test]
+    fn version_4_and_newer() {
+        for version in [4, 5] {
+            let cargo_lock = format!("version = {}\n", version);
+
+            let cargo = parse_cargo_lock_file(cargo_lock).expect("Valid Cargo.lock");
+
+            assert_eq!(cargo.version, Some(version));
+        }
+    }
+
+    #[test]
+    fn fail_for_unknown_format() {
+        assert_eq!(parse_cargo_lock_file("version = 0".to_string()).is_err(), true);
+        assert_eq!(parse_cargo_lock_file("version = \"three\"".to_string()).is_err(), true);
+        assert_eq!(parse_cargo_lock_file("[[package]]\nversion = \"1.0.0\"".to_string()).is_err(), true);
+    }
+}
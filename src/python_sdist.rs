@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use toml::Value;
+
+use crate::crate_archive::unpack_archive;
+
+// Python source distribution (`.tar.gz`) of package with Rust extension (e.g. `cryptography`),
+// extracted to temporary folder that is removed on drop
+pub struct PythonSdist {
+    folder: tempdir::TempDir,
+    // The package root inside the sdist, `<name>-<version>/`
+    root: PathBuf,
+}
+
+// Rust manifest that the build backend of the sdist uses, with the Cargo.lock of its workspace if exists
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdistCargoFiles {
+    pub(crate) cargo_file: PathBuf,
+    pub(crate) cargo_lock_file: Option<PathBuf>,
+}
+
+impl PythonSdist {
+    pub fn extract(sdist_path: &Path) -> Result<Self> {
+        // New folder with unique name, it is removed on drop even when the extraction fails
+        let folder = tempdir::TempDir::new("cargo-collect").context("Failed to create temporary folder")?;
+
+        let mut sdist = Self {
+            root: folder.path().to_path_buf(),
+            folder,
+        };
+
+        unpack_archive(sdist_path, sdist.folder.path())?;
+
+        // Sdist has single root folder, use the extraction folder for archive without one
+        let entries = fs::read_dir(sdist.folder.path())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect_vec();
+
+        if let [root] = entries.as_slice() {
+            if root.is_dir() {
+                sdist.root = root.clone();
+            }
+        }
+
+        Ok(sdist)
+    }
+
+    // The manifests are taken from maturin `manifest-path`, setuptools-rust extensions in pyproject.toml or setup.py,
+    // and `Cargo.toml` in the root of the sdist when the build backend does not specify one
+    pub fn find_cargo_files(&self) -> Result<Vec<SdistCargoFiles>> {
+        let mut manifest_paths = vec![];
+
+        let pyproject_path = self.root.join("pyproject.toml");
+
+        if pyproject_path.is_file() {
+            let content = fs::read_to_string(&pyproject_path)?;
+            let pyproject: Value = toml::from_str(content.as_str())
+                .with_context(|| format!("Failed to parse {}", pyproject_path.display()))?;
+
+            manifest_paths.extend(get_manifest_paths_from_pyproject(&pyproject));
+        }
+
+        let setup_path = self.root.join("setup.py");
+
+        if manifest_paths.is_empty() && setup_path.is_file() {
+            manifest_paths.extend(get_manifest_paths_from_setup_py(fs::read_to_string(&setup_path)?.as_str()));
+        }
+
+        if manifest_paths.is_empty() {
+            manifest_paths.push("Cargo.toml".to_string());
+        }
+
+        let mut visited = HashSet::new();
+        let mut cargo_files = vec![];
+
+        for manifest_path in manifest_paths {
+            let cargo_file = self.root.join(manifest_path.as_str());
+
+            if !cargo_file.is_file() {
+                return Err(anyhow!("Rust manifest {} of python sdist was not found", manifest_path));
+            }
+
+            if !visited.insert(cargo_file.clone()) {
+                continue;
+            }
+
+            cargo_files.push(SdistCargoFiles {
+                cargo_lock_file: self.find_cargo_lock_file(cargo_file.as_path()),
+                cargo_file,
+            });
+        }
+
+        Ok(cargo_files)
+    }
+
+    // Cargo.lock is next to the manifest or in its workspace root, which is inside the sdist
+    fn find_cargo_lock_file(&self, cargo_file: &Path) -> Option<PathBuf> {
+        cargo_file
+            .ancestors()
+            .skip(1)
+            .take_while(|folder| folder.starts_with(&self.root))
+            .map(|folder| folder.join("Cargo.lock"))
+            .find(|path| path.is_file())
+    }
+}
+
+fn get_manifest_paths_from_pyproject(pyproject: &Value) -> Vec<String> {
+    let tool = pyproject.get("tool");
+
+    // maturin defaults to Cargo.toml in the root, which is handled by the caller
+    if let Some(manifest_path) = tool
+        .and_then(|tool| tool.get("maturin"))
+        .and_then(|maturin| maturin.get("manifest-path"))
+        .and_then(|manifest_path| manifest_path.as_str())
+    {
+        return vec![manifest_path.to_string()];
+    }
+
+    let Some(setuptools_rust) = tool.and_then(|tool| tool.get("setuptools-rust")) else {
+        return vec![];
+    };
+
+    ["ext-modules", "bins"]
+        .iter()
+        .filter_map(|key| setuptools_rust.get(key))
+        .filter_map(|extensions| extensions.as_array())
+        .flatten()
+        .map(|extension| extension
+            .get("path")
+            .and_then(|path| path.as_str())
+            .unwrap_or("Cargo.toml")
+            .to_string())
+        .collect()
+}
+
+// `RustExtension("package._rust", "src/rust/Cargo.toml", ...)` or `path="src/rust/Cargo.toml"` in setup.py,
+// the string literals that end with `Cargo.toml` are taken as the manifests
+fn get_manifest_paths_from_setup_py(content: &str) -> Vec<String> {
+    if !content.contains("RustExtension") && !content.contains("RustBin") {
+        return vec![];
+    }
+
+    content
+        .split(['"', '\''])
+        .skip(1)
+        .step_by(2)
+        .filter(|literal| literal.ends_with("Cargo.toml"))
+        .map(|literal| literal.to_string())
+        .unique()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::crate_archive::test_utils::write_crate_archive;
+
+    // language=toml
+    const CARGO_FILE: &str = r#"
+[package]
+name = "rust-ext"
+version = "0.1.0"
+    "#;
+
+    fn cargo_files_relative_to_root(sdist: &PythonSdist) -> Vec<(PathBuf, Option<PathBuf>)> {
+        sdist.find_cargo_files()
+            .expect("Cargo files found")
+            .into_iter()
+            .map(|files| (
+                files.cargo_file.strip_prefix(&sdist.root).unwrap().to_path_buf(),
+                files.cargo_lock_file.map(|path| path.strip_prefix(&sdist.root).unwrap().to_path_buf()),
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn find_cargo_files_from_maturin_manifest_path() {
+        let tmp_dir = tempdir::TempDir::new("python_sdist_test").expect("Failed to create temp dir");
+
+        // language=toml
+        let sdist_path = write_crate_archive(tmp_dir.path(), "my-package", "1.0.0", &[
+            ("pyproject.toml", r#"
+[build-system]
+requires = ["maturin>=1.0"]
+build-backend = "maturin"
+
+[tool.maturin]
+manifest-path = "rust/crates/ext/Cargo.toml"
+            "#),
+            ("rust/Cargo.lock", "version = 3"),
+            ("rust/crates/ext/Cargo.toml", CARGO_FILE),
+        ]);
+
+        let sdist = PythonSdist::extract(sdist_path.as_path()).expect("Valid sdist");
+        let extract_folder = sdist.folder.path().to_path_buf();
+
+        assert_eq!(cargo_files_relative_to_root(&sdist), vec![
+            (PathBuf::from("rust/crates/ext/Cargo.toml"), Some(PathBuf::from("rust/Cargo.lock"))),
+        ]);
+
+        // The same sdist is extracted to a folder of its own each time
+        let other_sdist = PythonSdist::extract(sdist_path.as_path()).expect("Valid sdist");
+        assert_eq!(other_sdist.folder.path() == extract_folder.as_path(), false);
+
+        drop(sdist);
+        assert_eq!(extract_folder.exists(), false);
+    }
+
+    #[test]
+    fn find_cargo_files_from_setuptools_rust() {
+        let tmp_dir = tempdir::TempDir::new("python_sdist_test").expect("Failed to create temp dir");
+
+        // language=python
+        let sdist_path = write_crate_archive(tmp_dir.path(), "cryptography", "42.0.0", &[
+            ("setup.py", r#"
+from setuptools import setup
+from setuptools_rust import RustExtension
+
+setup(
+    rust_extensions=[
+        RustExtension(
+            "cryptography.hazmat.bindings._rust",
+            "src/rust/Cargo.toml",
+            py_limited_api=True,
+        )
+    ],
+)
+            "#),
+            ("src/rust/Cargo.toml", CARGO_FILE),
+        ]);
+
+        let sdist = PythonSdist::extract(sdist_path.as_path()).expect("Valid sdist");
+
+        assert_eq!(cargo_files_relative_to_root(&sdist), vec![
+            (PathBuf::from("src/rust/Cargo.toml"), None),
+        ]);
+
+        // language=toml
+        let sdist_path = write_crate_archive(tmp_dir.path(), "other", "1.0.0", &[
+            ("pyproject.toml", r#"
+[[tool.setuptools-rust.ext-modules]]
+target = "other._rust"
+path = "rust/Cargo.toml"
+
+[[tool.setuptools-rust.ext-modules]]
+target = "other._root"
+            "#),
+            ("rust/Cargo.toml", CARGO_FILE),
+            ("Cargo.toml", CARGO_FILE),
+            ("Cargo.lock", "version = 3"),
+        ]);
+
+        let sdist = PythonSdist::extract(sdist_path.as_path()).expect("Valid sdist");
+
+        assert_eq!(cargo_files_relative_to_root(&sdist), vec![
+            (PathBuf::from("rust/Cargo.toml"), Some(PathBuf::from("Cargo.lock"))),
+            (PathBuf::from("Cargo.toml"), Some(PathBuf::from("Cargo.lock"))),
+        ]);
+    }
+
+    #[test]
+    fn fail_for_missing_manifest() {
+        let tmp_dir = tempdir::TempDir::new("python_sdist_test").expect("Failed to create temp dir");
+
+        let sdist_path = write_crate_archive(tmp_dir.path(), "pure-python", "1.0.0", &[
+            ("setup.py", "from setuptools import setup\nsetup()"),
+        ]);
+
+        let sdist = PythonSdist::extract(sdist_path.as_path()).expect("Valid sdist");
+
+        assert_eq!(sdist.find_cargo_files().is_err(), true);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+// The subset of cargo config (`.cargo/config.toml`) and `credentials.toml` that is needed for registries
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct CargoConfig {
+    #[serde(default)]
+    pub registries: HashMap<String, RegistryConfigEntry>,
+
+    #[serde(default)]
+    pub registry: DefaultRegistryConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct RegistryConfigEntry {
+    pub index: Option<String>,
+
+    pub token: Option<String>,
+
+    pub credential_provider: Option<CredentialProvider>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct DefaultRegistryConfig {
+    pub global_credential_providers: Option<Vec<CredentialProvider>>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum CredentialProvider {
+    // Command line that is split by whitespace
+    CommandLine(String),
+    Args(Vec<String>),
+}
+
+impl CredentialProvider {
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            CredentialProvider::CommandLine(command_line) => command_line.split_whitespace().map(|arg| arg.to_string()).collect(),
+            CredentialProvider::Args(args) => args.clone(),
+        }
+    }
+}
+
+impl CargoConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    // Load the cargo config the same way cargo does,
+    // from `.cargo/config.toml` in the current directory and all its parents and then from `$CARGO_HOME/config.toml`
+    pub fn load(cwd: &Path, cargo_home: &Path) -> Result<Self> {
+        let mut config_files = cwd
+            .ancestors()
+            .map(|folder| folder.join(".cargo"))
+            .collect::<Vec<PathBuf>>();
+
+        if !config_files.iter().any(|folder| folder == cargo_home) {
+            config_files.push(cargo_home.to_path_buf());
+        }
+
+        let mut config = CargoConfig::default();
+
+        for folder in config_files {
+            if let Some(file_config) = Self::load_first_existing(&[folder.join("config.toml"), folder.join("config")])? {
+                config.merge(file_config);
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Load the registries tokens from `$CARGO_HOME/credentials.toml`
+    pub fn load_credentials(cargo_home: &Path) -> Result<Self> {
+        Ok(
+            Self::load_first_existing(&[cargo_home.join("credentials.toml"), cargo_home.join("credentials")])?
+                .unwrap_or_default()
+        )
+    }
+
+    fn load_first_existing(paths: &[PathBuf]) -> Result<Option<Self>> {
+        let Some(path) = paths.iter().find(|path| path.is_file()) else {
+            return Ok(None);
+        };
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cargo config file at {}", path.display()))?;
+
+        Self::parse(content.as_str())
+            .with_context(|| format!("Failed to parse cargo config file at {}", path.display()))
+            .map(Some)
+    }
+
+    // Merge config with lower priority, values that already exist are kept
+    pub fn merge(&mut self, other: CargoConfig) {
+        for (name, other_entry) in other.registries {
+            let entry = self.registries.entry(name).or_default();
+
+            entry.index = entry.index.take().or(other_entry.index);
+            entry.token = entry.token.take().or(other_entry.token);
+            entry.credential_provider = entry.credential_provider.take().or(other_entry.credential_provider);
+        }
+
+        self.registry.global_credential_providers = self.registry.global_credential_providers
+            .take()
+            .or(other.registry.global_credential_providers);
+    }
+
+    pub fn registry_index(&self, name: &str) -> Option<String> {
+        registry_env_var(name, "INDEX")
+            .or_else(|| self.registries.get(name)?.index.clone())
+    }
+
+    // Name of the configured registry with the index url
+    pub fn registry_name_by_index_url(&self, index_url: &str) -> Option<String> {
+        let index_url = normalize_index_url(index_url);
+
+        self.registries
+            .iter()
+            .filter(|(_, entry)| entry.index.as_deref().map(normalize_index_url) == Some(index_url))
+            .map(|(name, _)| name.clone())
+            .min()
+    }
+
+    pub fn registry_token(&self, name: &str) -> Option<String> {
+        self.registries.get(name)?.token.clone()
+    }
+
+    // The credential providers to try in order for the registry
+    pub fn credential_providers(&self, name: &str) -> Vec<CredentialProvider> {
+        if let Some(provider) = registry_env_var(name, "CREDENTIAL_PROVIDER") {
+            return vec![CredentialProvider::CommandLine(provider)];
+        }
+
+        if let Some(provider) = self.registries.get(name).and_then(|entry| entry.credential_provider.clone()) {
+            return vec![provider];
+        }
+
+        match &self.registry.global_credential_providers {
+            // Later providers take precedence
+            Some(providers) => providers.iter().rev().cloned().collect(),
+            None => vec![CredentialProvider::CommandLine("cargo:token".to_string())],
+        }
+    }
+}
+
+fn normalize_index_url(index_url: &str) -> &str {
+    index_url.trim_start_matches("registry+").trim_end_matches('/')
+}
+
+// Read `CARGO_REGISTRIES_<NAME>_<KEY>` environment variable
+pub fn registry_env_var(name: &str, key: &str) -> Option<String> {
+    std::env::var(format!("CARGO_REGISTRIES_{}_{}", name.to_uppercase().replace('-', "_"), key)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_registries() {
+        // language=toml
+        let config = CargoConfig::parse(r#"
+[registries.my-registry]
+index = "sparse+https://my.registry/index/"
+credential-provider = "cargo:token-from-stdout cat token.txt"
+
+[registries.other]
+index = "https://my.registry/git-index"
+
+[registry]
+global-credential-providers = ["cargo:token", ["/usr/bin/provider", "--flag with space"]]
+
+[build]
+jobs = 4
+        "#).expect("Valid config");
+
+        assert_eq!(config.registry_index("my-registry"), Some("sparse+https://my.registry/index/".to_string()));
+        assert_eq!(config.registry_index("other"), Some("https://my.registry/git-index".to_string()));
+        assert_eq!(config.registry_index("not-exists"), None);
+
+        assert_eq!(config.registry_name_by_index_url("sparse+https://my.registry/index"), Some("my-registry".to_string()));
+        assert_eq!(config.registry_name_by_index_url("registry+https://my.registry/git-index"), Some("other".to_string()));
+        assert_eq!(config.registry_name_by_index_url("https://unknown.registry/"), None);
+
+        assert_eq!(
+            config.credential_providers("my-registry").iter().map(|provider| provider.args()).collect::<Vec<_>>(),
+            vec![vec!["cargo:token-from-stdout".to_string(), "cat".to_string(), "token.txt".to_string()]]
+        );
+        assert_eq!(
+            config.credential_providers("other").iter().map(|provider| provider.args()).collect::<Vec<_>>(),
+            vec![
+                vec!["/usr/bin/provider".to_string(), "--flag with space".to_string()],
+                vec!["cargo:token".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn default_credential_provider() {
+        let config = CargoConfig::parse("").expect("Valid config");
+
+        assert_eq!(
+            config.credential_providers("my-registry"),
+            vec![CredentialProvider::CommandLine("cargo:token".to_string())]
+        );
+    }
+
+    #[test]
+    fn closer_config_take_precedence() {
+        let tmp_dir = tempdir::TempDir::new("cargo_config_test").expect("Failed to create temp dir");
+        let cargo_home = tmp_dir.path().join("cargo_home");
+        let project = tmp_dir.path().join("project");
+
+        fs::create_dir_all(cargo_home.as_path()).expect("Failed to create dir");
+        fs::create_dir_all(project.join(".cargo")).expect("Failed to create dir");
+
+        // language=toml
+        fs::write(cargo_home.join("config.toml"), r#"
+[registries.first]
+index = "sparse+https://home.registry/"
+
+[registries.second]
+index = "sparse+https://second.registry/"
+        "#).expect("Failed to write file");
+
+        // language=toml
+        fs::write(project.join(".cargo/config.toml"), r#"
+[registries.first]
+index = "sparse+https://project.registry/"
+        "#).expect("Failed to write file");
+
+        let config = CargoConfig::load(project.as_path(), cargo_home.as_path()).expect("Valid config");
+
+        assert_eq!(config.registry_index("first"), Some("sparse+https://project.registry/".to_string()));
+        assert_eq!(config.registry_index("second"), Some("sparse+https://second.registry/".to_string()));
+    }
+
+    #[test]
+    fn load_credentials_file() {
+        let tmp_dir = tempdir::TempDir::new("cargo_config_test").expect("Failed to create temp dir");
+
+        // language=toml
+        fs::write(tmp_dir.path().join("credentials.toml"), r#"
+[registries.my-registry]
+token = "secret"
+        "#).expect("Failed to write file");
+
+        let credentials = CargoConfig::load_credentials(tmp_dir.path()).expect("Valid credentials");
+
+        assert_eq!(credentials.registry_token("my-registry"), Some("secret".to_string()));
+        assert_eq!(credentials.registry_token("other"), None);
+    }
+}
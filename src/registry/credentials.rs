@@ -0,0 +1,234 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::registry::cargo_config::{registry_env_var, CargoConfig};
+
+// Get the registry token the way cargo does (see https://doc.rust-lang.org/cargo/reference/registry-authentication.html)
+// by trying each of the credential providers of the registry until one has a token
+pub fn get_registry_token(name: &str, index_url: &str, config: &CargoConfig, credentials: &CargoConfig) -> Result<Option<String>> {
+    for provider in config.credential_providers(name) {
+        let args = provider.args();
+
+        let Some((command, provider_args)) = args.split_first() else {
+            continue;
+        };
+
+        let token = match command.as_str() {
+            "cargo:token" => registry_env_var(name, "TOKEN")
+                .or_else(|| credentials.registry_token(name))
+                .or_else(|| config.registry_token(name)),
+            "cargo:token-from-stdout" => Some(get_token_from_stdout(name, index_url, provider_args)?),
+            builtin if builtin.starts_with("cargo:") => {
+                warn!("Credential provider {} is not supported, skipping", builtin);
+                None
+            }
+            _ => get_token_from_credential_provider(name, index_url, command, provider_args)?,
+        };
+
+        if token.is_some() {
+            return Ok(token);
+        }
+    }
+
+    Ok(None)
+}
+
+fn get_token_from_stdout(name: &str, index_url: &str, args: &[String]) -> Result<String> {
+    let (command, command_args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("cargo:token-from-stdout for registry {} is missing the command", name))?;
+
+    let output = Command::new(command)
+        .args(command_args)
+        .env("CARGO_REGISTRY_INDEX_URL", index_url)
+        .env("CARGO_REGISTRY_NAME_OPT", name)
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run {} to get the token for registry {}", command, name))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("{} failed to get the token for registry {}: {}", command, name, output.status));
+    }
+
+    let token = String::from_utf8(output.stdout)?.trim().to_string();
+
+    if token.is_empty() {
+        return Err(anyhow!("{} returned empty token for registry {}", command, name));
+    }
+
+    Ok(token)
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialHello {
+    v: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+enum CredentialResponse {
+    Ok(CredentialResponseOk),
+    Err(CredentialResponseErr),
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialResponseOk {
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialResponseErr {
+    kind: String,
+    message: Option<String>,
+}
+
+// Get the token from external credential provider using the cargo credential provider protocol (version 1)
+// see https://doc.rust-lang.org/cargo/reference/credential-provider-protocol.html
+fn get_token_from_credential_provider(name: &str, index_url: &str, command: &str, args: &[String]) -> Result<Option<String>> {
+    let mut child = Command::new(command)
+        .args(args)
+        .arg("--cargo-plugin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run credential provider {} for registry {}", command, name))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+    let mut line = String::new();
+    stdout.read_line(&mut line)?;
+
+    let hello: CredentialHello = serde_json::from_str(line.as_str())
+        .with_context(|| format!("Credential provider {} sent invalid hello message: {}", command, line.trim()))?;
+
+    if !hello.v.contains(&1) {
+        return Err(anyhow!("Credential provider {} does not support protocol version 1, supported versions: {:?}", command, hello.v));
+    }
+
+    let request = serde_json::json!({
+        "v": 1,
+        "registry": {
+            "index-url": index_url,
+            "name": name,
+        },
+        "kind": "get",
+        "operation": "read",
+        "args": [],
+    });
+
+    writeln!(stdin, "{}", request)?;
+    stdin.flush()?;
+
+    line.clear();
+    stdout.read_line(&mut line)?;
+
+    // Closing stdin tells the provider to exit
+    drop(stdin);
+    child.wait()?;
+
+    let response: CredentialResponse = serde_json::from_str(line.as_str())
+        .with_context(|| format!("Credential provider {} sent invalid response: {}", command, line.trim()))?;
+
+    match response {
+        CredentialResponse::Ok(ok) => Ok(ok.token),
+        CredentialResponse::Err(err) if err.kind == "not-found" || err.kind == "url-not-supported" => Ok(None),
+        CredentialResponse::Err(err) => Err(anyhow!(
+            "Credential provider {} failed for registry {}: {} {}",
+            command,
+            name,
+            err.kind,
+            err.message.unwrap_or_default()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::registry::cargo_config::{CredentialProvider, RegistryConfigEntry};
+
+    #[test]
+    fn token_from_credentials_file() {
+        let config = CargoConfig::default();
+        // language=toml
+        let credentials = CargoConfig::parse(r#"
+[registries.credentials-test-registry]
+token = "secret"
+        "#).expect("Valid credentials");
+
+        let token = get_registry_token("credentials-test-registry", "sparse+https://my.registry/", &config, &credentials)
+            .expect("Failed to get token");
+
+        assert_eq!(token, Some("secret".to_string()));
+    }
+
+    #[test]
+    fn no_token() {
+        let token = get_registry_token("no-token-test-registry", "sparse+https://my.registry/", &CargoConfig::default(), &CargoConfig::default())
+            .expect("Failed to get token");
+
+        assert_eq!(token, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn token_from_stdout() {
+        // language=toml
+        let config = CargoConfig::parse(r#"
+[registries.stdout-test-registry]
+credential-provider = ["cargo:token-from-stdout", "sh", "-c", "echo $CARGO_REGISTRY_NAME_OPT-token"]
+        "#).expect("Valid config");
+
+        let token = get_registry_token("stdout-test-registry", "sparse+https://my.registry/", &config, &CargoConfig::default())
+            .expect("Failed to get token");
+
+        assert_eq!(token, Some("stdout-test-registry-token".to_string()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn token_from_credential_provider() {
+        use crate::registry::test_utils::write_script;
+
+        let tmp_dir = tempdir::TempDir::new("credentials_test").expect("Failed to create temp dir");
+
+        let provider = write_script(tmp_dir.path(), "provider.sh", r#"#!/bin/sh
+echo '{"v":[1]}'
+read request
+case "$request" in
+  *'"index-url":"sparse+https://my.registry/"'*) echo '{"Ok":{"kind":"get","token":"provider-token","cache":"session","operation_independent":true}}' ;;
+  *) echo '{"Err":{"kind":"not-found"}}' ;;
+esac
+"#);
+
+        let config = CargoConfig {
+            registries: [(
+                "provider-test-registry".to_string(),
+                RegistryConfigEntry {
+                    credential_provider: Some(CredentialProvider::Args(vec![
+                        provider.to_str().unwrap().to_string(),
+                    ])),
+                    ..Default::default()
+                },
+            )].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let token = get_registry_token("provider-test-registry", "sparse+https://my.registry/", &config, &CargoConfig::default())
+            .expect("Failed to get token");
+
+        assert_eq!(token, Some("provider-token".to_string()));
+
+        let token = get_registry_token("provider-test-registry", "sparse+https://other.registry/", &config, &CargoConfig::default())
+            .expect("Failed to get token");
+
+        assert_eq!(token, None);
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use crates_index::Crate;
+use serde::Deserialize;
+
+// External executable that provides the index entries of the crates, for registries that `crates-index` can't read.
+//
+// The provider is started once and talks JSON lines over stdin/stdout:
+// - The provider sends hello with the supported protocol versions: `{"v":[1]}`
+// - Each request asks for the versions of one crate: `{"v":1,"kind":"versions","name":"serde"}`
+// - The response has the index entries (same format as the registry index) with the download url of each version:
+//   `{"Ok":{"versions":[{"name":"serde","vers":"1.0.0",...,"download-url":"https://..."}]}}`
+//   or `{"Err":{"kind":"not-found"}}` when the crate does not exist, and `{"Err":{"kind":"other","message":"..."}}` on failure
+// - Closing stdin tells the provider to exit
+pub struct IndexProvider {
+    command: String,
+    // Shared with the blocking task that talks with the provider
+    process: Arc<Mutex<ProviderProcess>>,
+    // Download url of each (crate name, version) that the provider returned
+    download_urls: Mutex<HashMap<(String, String), String>>,
+}
+
+// Time the provider has to send its hello, so a provider that never answers does not hang the collection
+const HELLO_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct ProviderProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderHello {
+    v: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+enum ProviderResponse {
+    Ok(ProviderResponseOk),
+    Err(ProviderResponseErr),
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderResponseOk {
+    versions: Vec<ProviderVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderResponseErr {
+    kind: String,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderVersion {
+    #[serde(rename = "download-url")]
+    download_url: String,
+
+    // The index entry, kept as is for parsing with `crates-index`
+    #[serde(flatten)]
+    entry: serde_json::Map<String, serde_json::Value>,
+}
+
+impl IndexProvider {
+    // Start the provider from command line that is split by whitespace
+    pub fn start(command_line: &str) -> Result<Self> {
+        Self::start_with_hello_timeout(command_line, HELLO_TIMEOUT)
+    }
+
+    fn start_with_hello_timeout(command_line: &str, hello_timeout: Duration) -> Result<Self> {
+        let args = command_line.split_whitespace().collect::<Vec<&str>>();
+
+        let (command, command_args) = args
+            .split_first()
+            .ok_or_else(|| anyhow!("Index provider command is empty"))?;
+
+        let mut child = Command::new(command)
+            .args(command_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Failed to run index provider {}", command))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        // The pipe can't be read with timeout, so the hello is read on its own thread that hands the pipe back
+        let (sender, receiver) = mpsc::channel();
+        let reader_command = command.to_string();
+
+        thread::spawn(move || {
+            let mut stdout = stdout;
+            let line = read_line(&mut stdout, reader_command.as_str());
+            let _ = sender.send((stdout, line));
+        });
+
+        let (stdout, line) = match receiver.recv_timeout(hello_timeout) {
+            Ok((stdout, line)) => (stdout, line),
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!("Index provider {} did not send hello in {} seconds", command, hello_timeout.as_secs_f32()));
+            }
+        };
+
+        let process = ProviderProcess {
+            child,
+            stdin: Some(stdin),
+            stdout,
+        };
+
+        let line = line?;
+
+        let hello: ProviderHello = serde_json::from_str(line.as_str())
+            .with_context(|| format!("Index provider {} sent invalid hello message: {}", command, line.trim()))?;
+
+        if !hello.v.contains(&1) {
+            return Err(anyhow!("Index provider {} does not support protocol version 1, supported versions: {:?}", command, hello.v));
+        }
+
+        Ok(Self {
+            command: command.to_string(),
+            process: Arc::new(Mutex::new(process)),
+            download_urls: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn crate_(&self, crate_name: &str) -> Result<Option<Crate>> {
+        let request = serde_json::json!({
+            "v": 1,
+            "kind": "versions",
+            "name": crate_name,
+        });
+
+        let process = self.process.clone();
+        let command = self.command.clone();
+
+        // The pipes are blocking, so the provider is read outside of the async runtime threads
+        let line = tokio::task::spawn_blocking(move || {
+            let mut process = process.lock().expect("Index provider lock is poisoned");
+            process.write_line(request.to_string().as_str())?;
+            process.read_line(command.as_str())
+        })
+            .await??;
+
+        let response: ProviderResponse = serde_json::from_str(line.as_str())
+            .with_context(|| format!("Index provider {} sent invalid response: {}", self.command, line.trim()))?;
+
+        let versions = match response {
+            ProviderResponse::Ok(ok) => ok.versions,
+            ProviderResponse::Err(err) if err.kind == "not-found" => return Ok(None),
+            ProviderResponse::Err(err) => return Err(anyhow!(
+                "Index provider {} failed to get crate {}: {} {}",
+                self.command,
+                crate_name,
+                err.kind,
+                err.message.unwrap_or_default()
+            )),
+        };
+
+        if versions.is_empty() {
+            return Ok(None);
+        }
+
+        let mut index_lines = vec![];
+        let mut download_urls = self.download_urls.lock().expect("Index provider lock is poisoned");
+
+        for version in versions {
+            let version_number = version.entry
+                .get("vers")
+                .and_then(|vers| vers.as_str())
+                .ok_or_else(|| anyhow!("Index provider {} sent version of crate {} without `vers`", self.command, crate_name))?;
+
+            download_urls.insert((crate_name.to_string(), version_number.to_string()), version.download_url);
+            index_lines.push(serde_json::Value::Object(version.entry).to_string());
+        }
+
+        let krate = Crate::from_slice(index_lines.join("\n").as_bytes())
+            .with_context(|| format!("Index provider {} sent invalid index entries for crate {}", self.command, crate_name))?;
+
+        Ok(Some(krate))
+    }
+
+    // Download url of version that was returned by the provider
+    pub fn download_url(&self, crate_name: &str, version: &str) -> Option<String> {
+        self.download_urls
+            .lock()
+            .expect("Index provider lock is poisoned")
+            .get(&(crate_name.to_string(), version.to_string()))
+            .cloned()
+    }
+}
+
+impl ProviderProcess {
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        let stdin = self.stdin.as_mut().expect("stdin is open until the provider is dropped");
+
+        writeln!(stdin, "{}", line)?;
+        stdin.flush()?;
+
+        Ok(())
+    }
+
+    fn read_line(&mut self, command: &str) -> Result<String> {
+        read_line(&mut self.stdout, command)
+    }
+}
+
+fn read_line(stdout: &mut BufReader<ChildStdout>, command: &str) -> Result<String> {
+    let mut line = String::new();
+
+    if stdout.read_line(&mut line)? == 0 {
+        return Err(anyhow!("Index provider {} exited unexpectedly", command));
+    }
+
+    Ok(line)
+}
+
+impl Drop for IndexProvider {
+    fn drop(&mut self) {
+        // Dropping must not wait for a request that is stuck on the provider or panic on a request that panicked
+        let mut process = match self.process.try_lock() {
+            Ok(process) => process,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+
+        // Closing stdin tells the provider to exit, it is killed so a stuck provider does not block the exit
+        process.stdin.take();
+        let _ = process.child.kill();
+        let _ = process.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn get_crates_from_provider() {
+        use crate::registry::test_utils::{write_fake_index_provider, FAKE_PROVIDER_CHECKSUM};
+
+        let tmp_dir = tempdir::TempDir::new("index_provider_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+
+        let provider = IndexProvider::start(provider_path.to_str().unwrap()).expect("Provider started");
+
+        let krate = provider
+            .crate_("foo")
+            .await
+            .expect("Valid response")
+            .expect("Crate exists");
+
+        assert_eq!(
+            krate.versions().iter().map(|version| version.version()).collect::<Vec<_>>(),
+            vec!["1.0.0", "1.1.0"]
+        );
+        assert_eq!(
+            krate.versions()[1].dependencies().iter().map(|dep| (dep.crate_name(), dep.requirement())).collect::<Vec<_>>(),
+            vec![("bar", "^0.1")]
+        );
+        assert_eq!(hex::encode(krate.versions()[1].checksum()), FAKE_PROVIDER_CHECKSUM);
+
+        assert_eq!(provider.download_url("foo", "1.1.0"), Some("https://store.local/foo/1.1.0.crate".to_string()));
+        assert_eq!(provider.download_url("foo", "2.0.0"), None);
+
+        assert_eq!(provider.crate_("not-exists").await.expect("Valid response").is_none(), true);
+        assert_eq!(provider.crate_("broken").await.is_err(), true);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn fail_for_provider_without_hello() {
+        use std::time::Instant;
+
+        use crate::registry::test_utils::write_script;
+
+        let tmp_dir = tempdir::TempDir::new("index_provider_test").expect("Failed to create temp dir");
+        let provider_path = write_script(tmp_dir.path(), "silent-provider.sh", "#!/bin/sh\nexec sleep 60\n");
+
+        let started = Instant::now();
+        let result = IndexProvider::start_with_hello_timeout(provider_path.to_str().unwrap(), Duration::from_millis(200));
+
+        assert_eq!(result.is_err(), true);
+        assert_eq!(started.elapsed() < Duration::from_secs(10), true);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn drop_provider_with_poisoned_lock() {
+        use crate::registry::test_utils::write_fake_index_provider;
+
+        let tmp_dir = tempdir::TempDir::new("index_provider_test").expect("Failed to create temp dir");
+        let provider_path = write_fake_index_provider(tmp_dir.path());
+
+        let provider = IndexProvider::start(provider_path.to_str().unwrap()).expect("Provider started");
+
+        let process = provider.process.clone();
+        let _ = thread::spawn(move || {
+            let _process = process.lock().unwrap();
+            panic!("Poison the provider lock");
+        }).join();
+
+        assert_eq!(provider.process.is_poisoned(), true);
+        drop(provider);
+    }
+
+    #[test]
+    fn fail_for_missing_provider() {
+        assert_eq!(IndexProvider::start("/not/exists/provider").is_err(), true);
+        assert_eq!(IndexProvider::start("  ").is_err(), true);
+    }
+}
@@ -0,0 +1,513 @@
+pub mod cargo_config;
+pub mod credentials;
+pub mod index_provider;
+
+use anyhow::{anyhow, Context, Result};
+use crates_index::{Crate, GitIndex, SparseIndex};
+use reqwest::header::{HeaderValue, AUTHORIZATION, USER_AGENT};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use crate::parse_cargo_files::package_source::{is_crates_io_index_url, CRATES_IO_INDEX_URL, CRATES_IO_SPARSE_INDEX_URL};
+use crate::registry::cargo_config::CargoConfig;
+use crate::registry::credentials::get_registry_token;
+use crate::registry::index_provider::IndexProvider;
+
+pub const CRATES_IO_REGISTRY_NAME: &str = "crates-io";
+
+enum RegistryIndex {
+    Git(Box<GitIndex>),
+    Sparse(SparseIndex),
+    Provider(IndexProvider),
+}
+
+// The registry `config.json` (see https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct RegistryConfig {
+    pub dl: String,
+
+    pub api: Option<String>,
+
+    #[serde(default)]
+    pub auth_required: bool,
+}
+
+impl RegistryConfig {
+    pub fn download_url(&self, crate_name: &str, version: &str) -> Option<String> {
+        crates_index::IndexConfig {
+            dl: self.dl.clone(),
+            api: self.api.clone(),
+        }.download_url(crate_name, version)
+    }
+}
+
+pub struct Registry {
+    pub(crate) name: String,
+    pub(crate) index_url: String,
+    index: RegistryIndex,
+    config: RegistryConfig,
+    token: Option<String>,
+    client: Client,
+}
+
+impl Registry {
+    // The default crates.io registry (or its replacement in cargo config)
+    pub fn crates_io() -> Result<Self> {
+        let index = GitIndex::new_cargo_default()?;
+        let index_config = index.index_config()?;
+
+        Ok(Self {
+            name: CRATES_IO_REGISTRY_NAME.to_string(),
+            index_url: CRATES_IO_INDEX_URL.to_string(),
+            index: RegistryIndex::Git(Box::new(index)),
+            config: RegistryConfig {
+                dl: index_config.dl,
+                api: index_config.api,
+                auth_required: false,
+            },
+            token: None,
+            client: Client::new(),
+        })
+    }
+
+    // crates.io through its sparse index, opening it only fetches the `config.json` instead of cloning the whole index
+    pub async fn crates_io_sparse() -> Result<Self> {
+        let (config, credentials) = load_cargo_config()?;
+
+        Self::open(Some(CRATES_IO_REGISTRY_NAME), CRATES_IO_SPARSE_INDEX_URL.to_string(), &config, &credentials).await
+    }
+
+    // Registry that its index entries are taken from external index provider
+    pub fn from_index_provider(command_line: &str) -> Result<Self> {
+        let index = IndexProvider::start(command_line)?;
+
+        Ok(Self {
+            name: command_line.to_string(),
+            index_url: format!("provider+{}", command_line),
+            index: RegistryIndex::Provider(index),
+            // The download url of each version comes from the provider
+            config: RegistryConfig {
+                dl: String::new(),
+                api: None,
+                auth_required: false,
+            },
+            token: None,
+            client: Client::new(),
+        })
+    }
+
+    // Registry that is configured in cargo config under `[registries.<name>]`
+    pub async fn from_name(name: &str) -> Result<Self> {
+        let (config, credentials) = load_cargo_config()?;
+
+        let index_url = config
+            .registry_index(name)
+            .ok_or_else(|| anyhow!("Registry {} is not configured, add it to cargo config under [registries.{}]", name, name))?;
+
+        Self::open(Some(name), index_url, &config, &credentials).await
+    }
+
+    // Registry by its index url, the name (used for finding the token) is taken from cargo config if configured there
+    pub async fn from_index_url(index_url: &str) -> Result<Self> {
+        if is_crates_io_index_url(index_url) {
+            return Self::crates_io();
+        }
+
+        let (config, credentials) = load_cargo_config()?;
+        let name = config.registry_name_by_index_url(index_url);
+
+        Self::open(name.as_deref(), index_url.to_string(), &config, &credentials).await
+    }
+
+    async fn open(name: Option<&str>, index_url: String, config: &CargoConfig, credentials: &CargoConfig) -> Result<Self> {
+        let display_name = name.unwrap_or(index_url.as_str()).to_string();
+        let client = Client::new();
+
+        let get_token = || match name {
+            Some(name) => get_registry_token(name, index_url.as_str(), config, credentials),
+            None => Err(anyhow!(
+                "Registry at {} requires authentication, add it to cargo config under [registries.<name>] to configure the token",
+                index_url
+            )),
+        };
+
+        if !index_url.starts_with("sparse+") {
+            let index = GitIndex::from_url(index_url.as_str())
+                .with_context(|| format!("Failed to open the index of registry {} at {}", display_name, index_url))?;
+            let registry_config = read_git_index_config(index.path())
+                .with_context(|| format!("Invalid config.json in registry {}", display_name))?;
+
+            let token = if registry_config.auth_required {
+                let token = get_token()?;
+
+                if token.is_none() {
+                    return Err(anyhow!("Registry {} requires authentication but no token was found", display_name));
+                }
+
+                token
+            } else {
+                None
+            };
+
+            return Ok(Self {
+                name: display_name,
+                index_url,
+                index: RegistryIndex::Git(Box::new(index)),
+                config: registry_config,
+                token,
+                client,
+            });
+        }
+
+        let index = SparseIndex::from_url(index_url.as_str())
+            .with_context(|| format!("Invalid index url {} for registry {}", index_url, display_name))?;
+
+        let config_url = format!("{}config.json", index.url());
+
+        let mut token = None;
+        let mut response = send_get_request(&client, config_url.as_str(), None).await?;
+
+        // Registries that require authentication reject the config request without token
+        if response.status() == StatusCode::UNAUTHORIZED {
+            token = get_token()?;
+
+            if token.is_none() {
+                return Err(anyhow!("Registry {} requires authentication but no token was found", display_name));
+            }
+
+            response = send_get_request(&client, config_url.as_str(), token.as_deref()).await?;
+        }
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to get config.json of registry {}: {}", display_name, response.status()));
+        }
+
+        let registry_config: RegistryConfig = serde_json::from_slice(&response.bytes().await?)
+            .with_context(|| format!("Invalid config.json in registry {}", display_name))?;
+
+        if registry_config.auth_required && token.is_none() {
+            token = get_token()?;
+        }
+
+        Ok(Self {
+            name: display_name,
+            index_url,
+            index: RegistryIndex::Sparse(index),
+            config: registry_config,
+            token,
+            client,
+        })
+    }
+
+    // Registry with known config that never fetches from the network
+    #[cfg(test)]
+    pub fn with_config(name: &str, index_url: &str, config: RegistryConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            index_url: index_url.to_string(),
+            index: RegistryIndex::Sparse(SparseIndex::at_path(std::env::temp_dir(), index_url.to_string())),
+            config,
+            token: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn is_index_provider(&self) -> bool {
+        matches!(self.index, RegistryIndex::Provider(_))
+    }
+
+    pub fn update(&mut self) -> Result<()> {
+        match &mut self.index {
+            RegistryIndex::Git(index) => index.update()?,
+            // Sparse index and provider are always fetched from the registry
+            RegistryIndex::Sparse(_) | RegistryIndex::Provider(_) => {}
+        }
+
+        Ok(())
+    }
+
+    pub async fn crate_(&self, crate_name: &str) -> Result<Option<Crate>> {
+        match &self.index {
+            RegistryIndex::Git(index) => Ok(index.crate_(crate_name)),
+            RegistryIndex::Sparse(index) => {
+                let url = index
+                    .crate_url(crate_name)
+                    .ok_or_else(|| anyhow!("Invalid crate name {}", crate_name))?;
+
+                let response = send_get_request(&self.client, url.as_str(), self.auth_token()).await?;
+
+                match response.status() {
+                    StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => Ok(None),
+                    status if status.is_success() => {
+                        let body = response.bytes().await?;
+                        Ok(Some(Crate::from_slice(&body)?))
+                    }
+                    status => Err(anyhow!("Failed to get crate {} from registry {}: {}", crate_name, self.name, status)),
+                }
+            }
+            RegistryIndex::Provider(provider) => provider.crate_(crate_name).await,
+        }
+    }
+
+    pub fn download_url(&self, crate_name: &str, version: &str) -> Option<String> {
+        match &self.index {
+            RegistryIndex::Provider(provider) => provider.download_url(crate_name, version),
+            _ => self.config.download_url(crate_name, version),
+        }
+    }
+
+    // The token to send in the `Authorization` header, only sent when the registry requires it
+    pub fn auth_token(&self) -> Option<&str> {
+        if self.config.auth_required {
+            self.token.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
+// All the registries that are used in the collection, opened when first needed
+pub struct Registries {
+    default: Registry,
+    others: HashMap<String, Registry>,
+}
+
+impl Registries {
+    pub fn new(default: Registry) -> Self {
+        Self {
+            default,
+            others: HashMap::new(),
+        }
+    }
+
+    pub fn default_registry(&self) -> &Registry {
+        &self.default
+    }
+
+    // Registry that is opened already, taken by its index url
+    #[cfg(test)]
+    pub fn insert(&mut self, registry: Registry) {
+        self.others.insert(registry.index_url.clone(), registry);
+    }
+
+    // Get the registry by its index url, the default registry when missing.
+    // The index provider replaces crates.io, so crates.io packages are taken from it as well
+    pub async fn get(&mut self, index_url: Option<&str>) -> Result<&Registry> {
+        let index_url = match index_url {
+            Some(index_url) if self.default.is_index_provider() && is_crates_io_index_url(index_url) => return Ok(&self.default),
+            Some(index_url) if !is_same_index_url(index_url, self.default.index_url.as_str()) => index_url,
+            _ => return Ok(&self.default),
+        };
+
+        if !self.others.contains_key(index_url) {
+            let registry = Registry::from_index_url(index_url).await?;
+            self.others.insert(index_url.to_string(), registry);
+        }
+
+        Ok(&self.others[index_url])
+    }
+}
+
+pub(crate) fn is_same_index_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/') || (is_crates_io_index_url(a) && is_crates_io_index_url(b))
+}
+
+// `GitIndex::index_config` only keeps `dl` and `api`, so `config.json` is read from the latest fetched commit of the index
+fn read_git_index_config(index_path: &Path) -> Result<RegistryConfig> {
+    let head = run_git_in_index(index_path, &["log", "-1", "--format=%H", "--ignore-missing", "FETCH_HEAD", "origin/HEAD", "origin/master"])?;
+    let head = String::from_utf8(head)?;
+
+    if head.trim().is_empty() {
+        return Err(anyhow!("Index at {} does not have fetched commit", index_path.display()));
+    }
+
+    let content = run_git_in_index(index_path, &["show", format!("{}:config.json", head.trim()).as_str()])?;
+
+    Ok(serde_json::from_slice(&content)?)
+}
+
+fn run_git_in_index(index_path: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(index_path)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+// Index url of the registry that is configured in cargo config under `[registries.<name>]`
+pub fn configured_index_url(name: &str) -> Result<Option<String>> {
+    let (config, _) = load_cargo_config()?;
+
+    Ok(config.registry_index(name))
+}
+
+fn load_cargo_config() -> Result<(CargoConfig, CargoConfig)> {
+    let cargo_home = home::cargo_home()?;
+    let config = CargoConfig::load(std::env::current_dir()?.as_path(), cargo_home.as_path())?;
+    let credentials = CargoConfig::load_credentials(cargo_home.as_path())?;
+
+    Ok((config, credentials))
+}
+
+async fn send_get_request(client: &Client, url: &str, token: Option<&str>) -> Result<reqwest::Response> {
+    let mut request = client
+        .get(url)
+        .header(USER_AGENT, user_agent());
+
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, token);
+    }
+
+    Ok(request.send().await?)
+}
+
+pub fn user_agent() -> HeaderValue {
+    HeaderValue::from_str(&format!("CargoCollect/{}", env!("CARGO_PKG_VERSION"))).expect("user agent is valid header")
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::parse_cargo_files::package_source::CRATES_IO_INDEX_URL;
+
+    pub const FAKE_PROVIDER_CHECKSUM: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[cfg(unix)]
+    pub fn write_script(folder: &Path, name: &str, content: &str) -> PathBuf {
+        let path = folder.join(name);
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::write(&path, content).expect("Failed to write script");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("Failed to set script permissions");
+        path
+    }
+
+    // Dependencies by name, requirement and the index url of their registry when it is not the same registry
+    fn index_entry(name: &str, version: &str, deps: &[(&str, &str, Option<&str>)]) -> String {
+        let deps = deps
+            .iter()
+            .map(|(dep_name, req, registry)| format!(
+                r#"{{"name":"{}","req":"{}","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal","registry":{}}}"#,
+                dep_name,
+                req,
+                registry.map(|registry| format!(r#""{}""#, registry)).unwrap_or("null".to_string())
+            ))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            r#"{{"name":"{}","vers":"{}","deps":[{}],"cksum":"{}","features":{{}},"yanked":false,"download-url":"https://store.local/{}/{}.crate"}}"#,
+            name, version, deps, FAKE_PROVIDER_CHECKSUM, name, version
+        )
+    }
+
+    // Index provider with `foo` (1.0.0 and 1.1.0 that depends on `bar`), `bar` 0.1.2, `broken` that always fails
+    // and `private` 0.2.0 that depends on `bar` and on `serde` from crates.io
+    #[cfg(unix)]
+    pub fn write_fake_index_provider(folder: &Path) -> PathBuf {
+        write_script(folder, "index-provider.sh", format!(r#"#!/bin/sh
+echo '{{"v":[1]}}'
+while read request; do
+  case "$request" in
+    *'"name":"foo"'*) echo '{{"Ok":{{"versions":[{},{}]}}}}' ;;
+    *'"name":"bar"'*) echo '{{"Ok":{{"versions":[{}]}}}}' ;;
+    *'"name":"private"'*) echo '{{"Ok":{{"versions":[{}]}}}}' ;;
+    *'"name":"broken"'*) echo '{{"Err":{{"kind":"other","message":"store is down"}}}}' ;;
+    *) echo '{{"Err":{{"kind":"not-found"}}}}' ;;
+  esac
+done
+"#,
+            index_entry("foo", "1.0.0", &[]),
+            index_entry("foo", "1.1.0", &[("bar", "^0.1", None)]),
+            index_entry("bar", "0.1.2", &[]),
+            index_entry("private", "0.2.0", &[("bar", "^0.1", None), ("serde", "^1", Some(CRATES_IO_INDEX_URL))]),
+        ).as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_registry_config() {
+        // language=json
+        let config: RegistryConfig = serde_json::from_str(r#"
+{
+    "dl": "https://my.registry/api/v1/crates",
+    "api": "https://my.registry",
+    "auth-required": true
+}
+        "#).expect("Valid config");
+
+        assert_eq!(config, RegistryConfig {
+            dl: "https://my.registry/api/v1/crates".to_string(),
+            api: Some("https://my.registry".to_string()),
+            auth_required: true,
+        });
+        assert_eq!(
+            config.download_url("serde", "1.0.0"),
+            Some("https://my.registry/api/v1/crates/serde/1.0.0/download".to_string())
+        );
+    }
+
+    #[test]
+    fn read_config_of_git_index() {
+        let tmp_dir = tempdir::TempDir::new("git_index_config").expect("Failed to create temp dir");
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(tmp_dir.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@test"])
+                .args(args)
+                .output()
+                .expect("Failed to run git")
+                .status;
+
+            assert_eq!(status.success(), true, "git {:?} failed", args);
+        };
+
+        // language=json
+        std::fs::write(
+            tmp_dir.path().join("config.json"),
+            r#"{ "dl": "https://my.registry/api/v1/crates", "auth-required": true }"#,
+        ).expect("Failed to write file");
+
+        git(&["init", "--quiet"]);
+        git(&["add", "config.json"]);
+        git(&["commit", "--quiet", "-m", "config"]);
+        // Like the fetched index, that only has the remote refs
+        git(&["update-ref", "refs/remotes/origin/master", "HEAD"]);
+
+        let config = read_git_index_config(tmp_dir.path().join(".git").as_path()).expect("Failed to read config");
+
+        assert_eq!(config, RegistryConfig {
+            dl: "https://my.registry/api/v1/crates".to_string(),
+            api: None,
+            auth_required: true,
+        });
+    }
+
+    #[test]
+    fn auth_not_required_by_default() {
+        // language=json
+        let config: RegistryConfig = serde_json::from_str(r#"{ "dl": "https://static.crates.io/crates" }"#).expect("Valid config");
+
+        assert_eq!(config.auth_required, false);
+    }
+}
@@ -0,0 +1,279 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::collect_packages::LockedPackage;
+use crate::parse_cargo_files::package_source::CRATES_IO_INDEX_URL;
+
+// Only the fields of CycloneDX and SPDX JSON documents that identify the cargo packages
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Sbom {
+    #[serde(rename_all = "camelCase")]
+    Spdx {
+        #[allow(dead_code)]
+        spdx_version: String,
+        #[serde(default)]
+        packages: Vec<SpdxPackage>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CycloneDx {
+        #[allow(dead_code)]
+        bom_format: String,
+        #[serde(default)]
+        components: Vec<CycloneDxComponent>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct CycloneDxComponent {
+    purl: Option<String>,
+    #[serde(default)]
+    hashes: Vec<CycloneDxHash>,
+    // Components can be nested in their parent component
+    #[serde(default)]
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CycloneDxHash {
+    alg: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    #[serde(default)]
+    external_refs: Vec<SpdxExternalRef>,
+    #[serde(default)]
+    checksums: Vec<SpdxChecksum>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_type: String,
+    reference_locator: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum {
+    algorithm: String,
+    checksum_value: String,
+}
+
+// Name and version of `pkg:cargo/name@version` package url
+#[derive(Debug, PartialEq)]
+struct CargoPurl {
+    name: String,
+    version: String,
+    repository_url: Option<String>,
+}
+
+// Every component of the SBOM with cargo package url, with its SHA-256 checksum when the SBOM has one
+pub fn read_sbom(sbom_path: &Path) -> Result<Vec<LockedPackage>> {
+    let content = fs::read_to_string(sbom_path)
+        .with_context(|| format!("Failed to read SBOM file at {}", sbom_path.display()))?;
+
+    parse_sbom(&content).with_context(|| format!("Failed to parse SBOM file at {}", sbom_path.display()))
+}
+
+fn parse_sbom(content: &str) -> Result<Vec<LockedPackage>> {
+    let sbom: Sbom = serde_json::from_str(content)
+        .context("Only CycloneDX and SPDX JSON documents are supported")?;
+
+    let purls_with_checksums = match sbom {
+        Sbom::Spdx { packages, .. } => packages
+            .into_iter()
+            .flat_map(|package| {
+                let checksum = package.checksums
+                    .into_iter()
+                    .find(|checksum| checksum.algorithm == "SHA256")
+                    .map(|checksum| checksum.checksum_value);
+
+                package.external_refs
+                    .into_iter()
+                    .filter(|external_ref| external_ref.reference_type == "purl")
+                    .map(move |external_ref| (external_ref.reference_locator, checksum.clone()))
+            })
+            .collect(),
+        Sbom::CycloneDx { components, .. } => {
+            let mut purls_with_checksums = vec![];
+            collect_cyclonedx_purls(components, &mut purls_with_checksums);
+            purls_with_checksums
+        }
+    };
+
+    let mut locked_packages = vec![];
+
+    for (purl, checksum) in purls_with_checksums {
+        let Some(purl) = parse_cargo_purl(purl.as_str())? else {
+            continue;
+        };
+
+        // Cargo package urls point to crates.io unless they have other repository
+        if purl.repository_url.as_deref().is_some_and(|url| url.trim_end_matches('/') != "https://crates.io") {
+            warn!("Skipping {}@{} from {}, only crates.io packages are collected from SBOM", purl.name, purl.version, purl.repository_url.unwrap());
+            continue;
+        }
+
+        locked_packages.push(LockedPackage {
+            name: purl.name,
+            version: purl.version,
+            registry: CRATES_IO_INDEX_URL.to_string(),
+            checksum,
+        });
+    }
+
+    Ok(locked_packages)
+}
+
+fn collect_cyclonedx_purls(components: Vec<CycloneDxComponent>, purls_with_checksums: &mut Vec<(String, Option<String>)>) {
+    for component in components {
+        if let Some(purl) = component.purl {
+            let checksum = component.hashes
+                .into_iter()
+                .find(|hash| hash.alg == "SHA-256")
+                .map(|hash| hash.content);
+
+            purls_with_checksums.push((purl, checksum));
+        }
+
+        collect_cyclonedx_purls(component.components, purls_with_checksums);
+    }
+}
+
+// Package url of other ecosystems is None
+fn parse_cargo_purl(purl: &str) -> Result<Option<CargoPurl>> {
+    let Some(rest) = purl.strip_prefix("pkg:cargo/") else {
+        return Ok(None);
+    };
+
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (name_and_version, qualifiers) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let (name, version) = name_and_version
+        .split_once('@')
+        .ok_or_else(|| anyhow!("Package url {} does not have version", purl))?;
+
+    let repository_url = qualifiers
+        .split('&')
+        .filter_map(|qualifier| qualifier.split_once('='))
+        .find(|(key, _)| *key == "repository_url")
+        .map(|(_, value)| percent_decode(value));
+
+    Ok(Some(CargoPurl {
+        name: percent_decode(name),
+        version: percent_decode(version),
+        repository_url,
+    }))
+}
+
+// Package url components are percent-encoded, e.g. `+` of build metadata is `%2B`
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex_byte = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match hex_byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn package(name: &str, version: &str, checksum: Option<&str>) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            registry: CRATES_IO_INDEX_URL.to_string(),
+            checksum: checksum.map(|checksum| checksum.to_string()),
+        }
+    }
+
+    #[test]
+    fn parse_cyclonedx_sbom() {
+        // language=json
+        let content = r#"{
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "components": [
+                {
+                    "type": "library",
+                    "name": "serde",
+                    "purl": "pkg:cargo/serde@1.0.188",
+                    "hashes": [{ "alg": "SHA-256", "content": "cf9e0fcba69a370eed61bcf2b728575f726b50b55cba78064753d708ddc7549e" }],
+                    "components": [
+                        { "type": "library", "name": "semver", "purl": "pkg:cargo/semver@1.0.0%2Bmeta" }
+                    ]
+                },
+                { "type": "library", "name": "requests", "purl": "pkg:pypi/requests@2.31.0" },
+                { "type": "library", "name": "private", "purl": "pkg:cargo/private@0.1.0?repository_url=https://my-registry.local" },
+                { "type": "application", "name": "app" }
+            ]
+        }"#;
+
+        assert_eq!(parse_sbom(content).unwrap(), vec![
+            package("serde", "1.0.188", Some("cf9e0fcba69a370eed61bcf2b728575f726b50b55cba78064753d708ddc7549e")),
+            package("semver", "1.0.0+meta", None),
+        ]);
+    }
+
+    #[test]
+    fn parse_spdx_sbom() {
+        // language=json
+        let content = r#"{
+            "spdxVersion": "SPDX-2.3",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "packages": [
+                {
+                    "name": "itoa",
+                    "SPDXID": "SPDXRef-itoa",
+                    "checksums": [
+                        { "algorithm": "SHA1", "checksumValue": "da39a3ee5e6b4b0d3255bfef95601890afd80709" },
+                        { "algorithm": "SHA256", "checksumValue": "af150ab688ff2122fcef229be89cb50dd66af9e01a4ff320cc137eecc9bacc38" }
+                    ],
+                    "externalRefs": [
+                        { "referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": "pkg:cargo/itoa@1.0.9" }
+                    ]
+                },
+                { "name": "root", "SPDXID": "SPDXRef-root" }
+            ]
+        }"#;
+
+        assert_eq!(parse_sbom(content).unwrap(), vec![
+            package("itoa", "1.0.9", Some("af150ab688ff2122fcef229be89cb50dd66af9e01a4ff320cc137eecc9bacc38")),
+        ]);
+    }
+
+    #[test]
+    fn fail_for_cargo_purl_without_version() {
+        assert_eq!(parse_cargo_purl("pkg:cargo/serde").is_err(), true);
+        assert_eq!(parse_cargo_purl("pkg:npm/left-pad@1.0.0").unwrap(), None);
+    }
+}
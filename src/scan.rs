@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use itertools::Itertools;
+
+// Rust projects that were found by scanning folder tree
+#[derive(Debug, Default, PartialEq)]
+pub struct ScannedProjects {
+    pub(crate) cargo_lock_files: Vec<PathBuf>,
+    // Manifests of projects without Cargo.lock
+    pub(crate) cargo_files: Vec<PathBuf>,
+}
+
+// Find every Cargo.lock and every Cargo.toml that no Cargo.lock in its folder or above covers,
+// files that are ignored by `.gitignore` (e.g. `target`) and hidden folders are skipped
+pub fn scan_folder(folder: &Path) -> Result<ScannedProjects> {
+    let mut cargo_lock_files = vec![];
+    let mut cargo_files = vec![];
+
+    // Apply `.gitignore` also outside of git repositories
+    for entry in WalkBuilder::new(folder).require_git(false).build() {
+        let entry = entry.with_context(|| format!("Failed to scan {}", folder.display()))?;
+
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+
+        match entry.file_name().to_str() {
+            Some("Cargo.lock") => cargo_lock_files.push(entry.into_path()),
+            Some("Cargo.toml") => cargo_files.push(entry.into_path()),
+            _ => {}
+        }
+    }
+
+    let is_covered_by_lock_file = |cargo_file: &PathBuf| {
+        cargo_file
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(folder))
+            .any(|ancestor| cargo_lock_files.contains(&ancestor.join("Cargo.lock")))
+    };
+
+    let cargo_files = cargo_files
+        .iter()
+        .filter(|cargo_file| !is_covered_by_lock_file(cargo_file))
+        .cloned()
+        .sorted()
+        .collect();
+
+    cargo_lock_files.sort();
+
+    Ok(ScannedProjects {
+        cargo_lock_files,
+        cargo_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn scan_lock_files_and_standalone_cargo_files() {
+        let tmp_dir = tempdir::TempDir::new("scan_test").expect("Failed to create temp dir");
+        let root = tmp_dir.path();
+
+        for file in [
+            "locked/Cargo.toml",
+            "locked/Cargo.lock",
+            "locked/crates/member/Cargo.toml",
+            "locked/target/package/copy/Cargo.toml",
+            "standalone/Cargo.toml",
+            "ignored/Cargo.lock",
+            ".hidden/Cargo.lock",
+            "other/nested/Cargo.lock",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Failed to create dir");
+            fs::write(path, "").expect("Failed to write file");
+        }
+
+        fs::write(root.join(".gitignore"), "ignored/\ntarget/\n").expect("Failed to write file");
+
+        assert_eq!(scan_folder(root).expect("Scan successfully"), ScannedProjects {
+            cargo_lock_files: vec![root.join("locked/Cargo.lock"), root.join("other/nested/Cargo.lock")],
+            cargo_files: vec![root.join("standalone/Cargo.toml")],
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use cargo_platform::{Cfg, Platform};
+use tracing::warn;
+
+// The platforms to collect the target specific dependencies for, dependencies of all platforms are collected when empty
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TargetFilter {
+    // Target triple with its cfg values (e.g. `unix`, `target_os="linux"`)
+    targets: Vec<(String, Vec<Cfg>)>,
+}
+
+impl TargetFilter {
+    // Filter by the target triples, their cfg values are taken from `rustc --print cfg`
+    pub fn from_targets(targets: &[String]) -> Result<Self> {
+        let targets = targets
+            .iter()
+            .map(|target| Ok((target.clone(), get_target_cfgs(target)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { targets })
+    }
+
+    #[cfg(test)]
+    pub fn with_cfgs(target: &str, cfgs: &[&str]) -> Self {
+        Self {
+            targets: vec![(
+                target.to_string(),
+                cfgs.iter().map(|cfg| Cfg::from_str(cfg).expect("Valid cfg")).collect(),
+            )],
+        }
+    }
+
+    // Whether the dependencies for the platform (target triple or `cfg(...)` expression) should be collected
+    pub fn matches(&self, platform: &str) -> bool {
+        if self.targets.is_empty() {
+            return true;
+        }
+
+        let platform = match Platform::from_str(platform) {
+            Ok(platform) => platform,
+            Err(e) => {
+                warn!("Invalid target platform {}, collecting its dependencies anyway: {}", platform, e);
+                return true;
+            }
+        };
+
+        self.targets
+            .iter()
+            .any(|(target, cfgs)| platform.matches(target, cfgs))
+    }
+}
+
+fn get_target_cfgs(target: &str) -> Result<Vec<Cfg>> {
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+
+    let output = Command::new(rustc.as_str())
+        .args(["--print", "cfg", "--target", target])
+        .output()
+        .with_context(|| format!("Failed to run {} to get the cfg values of target {}", rustc, target))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to get the cfg values of target {}: {}",
+            target,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| Cfg::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn match_all_without_targets() {
+        let filter = TargetFilter::default();
+
+        assert_eq!(filter.matches("cfg(windows)"), true);
+        assert_eq!(filter.matches("x86_64-pc-windows-msvc"), true);
+    }
+
+    #[test]
+    fn match_by_triple_and_cfg() {
+        let filter = TargetFilter::with_cfgs("x86_64-unknown-linux-gnu", &["unix", "target_os=\"linux\"", "target_arch=\"x86_64\""]);
+
+        assert_eq!(filter.matches("x86_64-unknown-linux-gnu"), true);
+        assert_eq!(filter.matches("x86_64-pc-windows-msvc"), false);
+        assert_eq!(filter.matches("cfg(unix)"), true);
+        assert_eq!(filter.matches("cfg(windows)"), false);
+        assert_eq!(filter.matches("cfg(all(target_os = \"linux\", not(target_arch = \"arm\")))"), true);
+    }
+
+    #[test]
+    fn cfgs_from_rustc() {
+        let filter = TargetFilter::from_targets(&["x86_64-unknown-linux-gnu".to_string()]).expect("Valid target");
+
+        assert_eq!(filter.matches("cfg(target_os = \"linux\")"), true);
+        assert_eq!(filter.matches("cfg(target_os = \"macos\")"), false);
+    }
+}